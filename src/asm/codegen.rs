@@ -0,0 +1,118 @@
+use crate::program::{Instruction, Program};
+use crate::value::Value;
+
+/// Accumulates Whitespace source code
+struct Emitter<'a> {
+    program: &'a Program,
+    source: String,
+}
+
+impl<'a> Emitter<'a> {
+    fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            source: String::new(),
+        }
+    }
+
+    /// Emits a sequence of tokens written as `S`, `T` and `L`
+    fn tokens(&mut self, tokens: &str) {
+        for token in tokens.chars() {
+            let c = match token {
+                'S' => ' ',
+                'T' => '\t',
+                'L' => '\n',
+                _ => unreachable!(),
+            };
+            self.source.push(c);
+        }
+    }
+
+    fn digits(&mut self, digits: &[bool]) {
+        for digit in digits {
+            self.source.push(if *digit { '\t' } else { ' ' });
+        }
+    }
+
+    /// Emits a number literal
+    fn number(&mut self, num: &Value) {
+        let (is_negative, digits) = num.to_binary();
+        self.tokens(if is_negative { "T" } else { "S" });
+        self.digits(&digits);
+        self.tokens("L");
+    }
+
+    /// Emits a label
+    fn label(&mut self, label: usize) {
+        let digits = format!("{:b}", label);
+        let digits: Vec<_> = digits.chars().map(|c| c == '1').collect();
+        self.digits(&digits);
+        self.tokens("L");
+    }
+
+    /// Emits a jump instruction, naming its target by the first label
+    /// marking it, or by a label that marks nothing if it was never resolved
+    fn jump(&mut self, prefix: &str, pc: usize) {
+        let label = self.program.labels_at(pc).first().copied();
+        let label = label.unwrap_or_else(|| self.program.unmarked_label());
+        self.tokens(prefix);
+        self.label(label);
+    }
+
+    fn label_marks(&mut self, pc: usize) {
+        for label in self.program.labels_at(pc) {
+            self.tokens("LSS");
+            self.label(label);
+        }
+    }
+
+    fn inst(&mut self, inst: &Instruction) {
+        match inst {
+            Instruction::Push(idx) => {
+                self.tokens("SS");
+                self.number(self.program.get_const(*idx));
+            }
+            Instruction::Dup => self.tokens("SLS"),
+            Instruction::Copy(n) => {
+                self.tokens("STS");
+                self.number(&Value::from(*n));
+            }
+            Instruction::Swap => self.tokens("SLT"),
+            Instruction::Pop => self.tokens("SLL"),
+            Instruction::Slide(n) => {
+                self.tokens("STL");
+                self.number(&Value::from(*n));
+            }
+            Instruction::Add => self.tokens("TSSS"),
+            Instruction::Subtract => self.tokens("TSST"),
+            Instruction::Multiply => self.tokens("TSSL"),
+            Instruction::Divide => self.tokens("TSTS"),
+            Instruction::Modulo => self.tokens("TSTT"),
+            Instruction::Store => self.tokens("TTS"),
+            Instruction::Retrieve => self.tokens("TTT"),
+            Instruction::Call(pc) => self.jump("LST", *pc),
+            Instruction::Jump(pc) => self.jump("LSL", *pc),
+            Instruction::JumpIfZero(pc) => self.jump("LTS", *pc),
+            Instruction::JumpIfNeg(pc) => self.jump("LTT", *pc),
+            Instruction::Return => self.tokens("LTL"),
+            Instruction::End => self.tokens("LLL"),
+            Instruction::OutputChar => self.tokens("TLSS"),
+            Instruction::OutputNum => self.tokens("TLST"),
+            Instruction::ReadChar => self.tokens("TLTS"),
+            Instruction::ReadNum => self.tokens("TLTT"),
+        }
+    }
+}
+
+/// Translates a program back into Whitespace source code. Every label
+/// marked in the program is kept, including ones that are never used
+pub fn to_whitespace(program: &Program) -> String {
+    let mut emitter = Emitter::new(program);
+    for (pc, inst) in program.instructions().iter().enumerate() {
+        emitter.label_marks(pc);
+        emitter.inst(inst);
+    }
+    emitter.label_marks(program.inst_count());
+
+    emitter.source
+}
@@ -0,0 +1,107 @@
+use crate::program::{Instruction, Program};
+use std::fmt::Write;
+
+/// The column that the comment on each instruction starts at
+const COMMENT_COLUMN: usize = 28;
+
+/// Returns the name a label is displayed with
+pub(crate) fn label_name(program: &Program, label: usize) -> String {
+    match program.label_name(label) {
+        Some(name) => name.to_string(),
+        None => format!("label_{}", label),
+    }
+}
+
+/// Returns the name of the label a jump to `pc` is displayed with. A jump
+/// that was never resolved, which only a program with parse errors has, is
+/// shown with a label that marks nothing
+fn target_name(program: &Program, pc: usize) -> String {
+    let label = program.labels_at(pc).first().copied();
+    label_name(program, label.unwrap_or_else(|| program.unmarked_label()))
+}
+
+/// Returns the name of the subroutine a call to `pc` is displayed with
+fn sub_name(program: &Program, pc: usize) -> String {
+    match program.get_label(pc) {
+        Some(label) => label_name(program, label),
+        None => target_name(program, pc),
+    }
+}
+
+/// Renders a single instruction as its mnemonic and operand
+pub(crate) fn mnemonic(program: &Program, inst: &Instruction) -> String {
+    match inst {
+        Instruction::Push(idx) => format!("push {}", program.get_const(*idx)),
+        Instruction::Dup => "dup".to_string(),
+        Instruction::Copy(n) => format!("copy {}", n),
+        Instruction::Swap => "swap".to_string(),
+        Instruction::Pop => "pop".to_string(),
+        Instruction::Slide(n) => format!("slide {}", n),
+        Instruction::Add => "add".to_string(),
+        Instruction::Subtract => "sub".to_string(),
+        Instruction::Multiply => "mul".to_string(),
+        Instruction::Divide => "div".to_string(),
+        Instruction::Modulo => "mod".to_string(),
+        Instruction::Store => "store".to_string(),
+        Instruction::Retrieve => "retrieve".to_string(),
+        Instruction::Call(pc) => format!("call {}", sub_name(program, *pc)),
+        Instruction::Jump(pc) => format!("jmp {}", target_name(program, *pc)),
+        Instruction::JumpIfZero(pc) => format!("jz {}", target_name(program, *pc)),
+        Instruction::JumpIfNeg(pc) => format!("jn {}", target_name(program, *pc)),
+        Instruction::Return => "ret".to_string(),
+        Instruction::End => "end".to_string(),
+        Instruction::OutputChar => "outc".to_string(),
+        Instruction::OutputNum => "outn".to_string(),
+        Instruction::ReadChar => "readc".to_string(),
+        Instruction::ReadNum => "readn".to_string(),
+    }
+}
+
+/// Writes out every label marking `pc`, preceded by a header if `pc` is
+/// the start of a subroutine
+fn label_marks(out: &mut String, program: &Program, pc: usize) {
+    if let Some(label) = program.get_label(pc) {
+        writeln!(out).unwrap();
+        writeln!(out, "; subroutine {}", label_name(program, label)).unwrap();
+    }
+
+    for label in program.labels_at(pc) {
+        writeln!(out, "{}:", label_name(program, label)).unwrap();
+    }
+}
+
+/// Renders a program as annotated mnemonics. Each instruction is commented
+/// with its index and source position, so the output can be assembled again
+/// with `assemble`
+pub fn disassemble(program: &Program) -> String {
+    let mut out = String::new();
+    writeln!(out, "; main").unwrap();
+
+    for (pc, inst) in program.instructions().iter().enumerate() {
+        label_marks(&mut out, program, pc);
+
+        let text = format!("    {}", mnemonic(program, inst));
+        let start = program.span_at(pc).start;
+        write!(
+            out,
+            "{:width$} ; {:04}  line {}:{}",
+            text,
+            pc,
+            start.line,
+            start.column,
+            width = COMMENT_COLUMN - 1
+        )
+        .unwrap();
+
+        if let Instruction::Push(idx) = inst {
+            let num = program.get_const(*idx).to_i64();
+            if let Some(c) = num.filter(|x| (32..127).contains(x)) {
+                write!(out, "  '{}'", c as u8 as char).unwrap();
+            }
+        }
+        writeln!(out).unwrap();
+    }
+    label_marks(&mut out, program, program.inst_count());
+
+    out
+}
@@ -0,0 +1,122 @@
+use crate::json::Object;
+use std::error::Error;
+use std::fmt;
+
+/// What kind of assembly error was found
+#[derive(Clone, Debug, PartialEq)]
+pub enum AsmErrorKind {
+    /// The mnemonic does not name any instruction
+    UnknownMnemonic(String),
+    /// The instruction requires an operand but none was given
+    MissingOperand,
+    /// The instruction was given more operands than it takes
+    UnexpectedOperand,
+    /// The operand could not be parsed as a number
+    InvalidNumber,
+    /// A `copy` or `slide` operand was negative
+    NegativeOperand,
+    /// The operand could not be parsed as a label name
+    InvalidLabelName,
+    /// A character literal was malformed or not terminated
+    InvalidCharLiteral,
+    /// The program jumps to a label that is never defined
+    UndefinedLabel(String),
+    /// The same label was defined more than once
+    DuplicateLabel(String),
+}
+
+impl AsmErrorKind {
+    /// Returns the snake case name of the kind, as used in JSON output
+    pub fn name(&self) -> &'static str {
+        match self {
+            AsmErrorKind::UnknownMnemonic(_) => "unknown_mnemonic",
+            AsmErrorKind::MissingOperand => "missing_operand",
+            AsmErrorKind::UnexpectedOperand => "unexpected_operand",
+            AsmErrorKind::InvalidNumber => "invalid_number",
+            AsmErrorKind::NegativeOperand => "negative_operand",
+            AsmErrorKind::InvalidLabelName => "invalid_label_name",
+            AsmErrorKind::InvalidCharLiteral => "invalid_char_literal",
+            AsmErrorKind::UndefinedLabel(_) => "undefined_label",
+            AsmErrorKind::DuplicateLabel(_) => "duplicate_label",
+        }
+    }
+}
+
+impl fmt::Display for AsmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmErrorKind::UnknownMnemonic(name) => write!(f, "Unknown instruction '{}'.", name),
+            AsmErrorKind::MissingOperand => write!(f, "Missing operand."),
+            AsmErrorKind::UnexpectedOperand => write!(f, "Unexpected operand."),
+            AsmErrorKind::InvalidNumber => write!(f, "Invalid number."),
+            AsmErrorKind::NegativeOperand => write!(f, "Operand must not be negative."),
+            AsmErrorKind::InvalidLabelName => write!(f, "Invalid label name."),
+            AsmErrorKind::InvalidCharLiteral => write!(f, "Invalid character literal."),
+            AsmErrorKind::UndefinedLabel(name) => write!(f, "Undefined label '{}'.", name),
+            AsmErrorKind::DuplicateLabel(name) => write!(f, "Label '{}' is already defined.", name),
+        }
+    }
+}
+
+/// An error encountered while assembling a program
+#[derive(Debug)]
+pub struct AsmError {
+    line_no: usize,
+    column: usize,
+    kind: AsmErrorKind,
+}
+
+impl AsmError {
+    pub fn new(kind: AsmErrorKind, line_no: usize, column: usize) -> Self {
+        Self {
+            line_no,
+            column,
+            kind,
+        }
+    }
+
+    /// Returns what kind of error was found
+    pub fn kind(&self) -> &AsmErrorKind {
+        &self.kind
+    }
+
+    /// Returns the source line number the error was found on
+    pub fn line_no(&self) -> usize {
+        self.line_no
+    }
+
+    /// Returns the column the error was found at
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Encodes the error as a single line JSON object
+    pub fn to_json(&self) -> String {
+        let mut object = Object::new()
+            .string("type", "asm_error")
+            .string("kind", self.kind.name());
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(name) => {
+                object = object.string("mnemonic", name);
+            }
+            AsmErrorKind::UndefinedLabel(name) | AsmErrorKind::DuplicateLabel(name) => {
+                object = object.string("label", name);
+            }
+            _ => {}
+        }
+
+        object
+            .string("message", &self.kind.to_string())
+            .number("line", self.line_no)
+            .number("column", self.column)
+            .finish()
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[Line {}:{}] {}", self.line_no, self.column, self.kind)
+    }
+}
+
+impl Error for AsmError {}
@@ -0,0 +1,409 @@
+//! An assembler for a human readable mnemonic form of Whitespace.
+//!
+//! Each line holds at most one instruction, optionally preceded by a label
+//! definition of the form `name:`. Everything after a `;` or `#` is a
+//! comment.
+//!
+//! ```text
+//! ; prints the numbers 1 through 10
+//!         push 1
+//! loop:   dup
+//!         outn
+//!         push '\n'
+//!         outc
+//!         push 1
+//!         add
+//!         dup
+//!         push 11
+//!         sub
+//!         jz done
+//!         jmp loop
+//! done:   end
+//! ```
+//!
+//! The supported instructions are `push n`, `dup`, `copy n`, `swap`, `pop`,
+//! `slide n`, `add`, `sub`, `mul`, `div`, `mod`, `store`, `retrieve`,
+//! `label name`, `call name`, `jmp name`, `jz name`, `jn name`, `ret`,
+//! `end`, `outc`, `outn`, `readc` and `readn`. Numbers may be written in
+//! decimal or as character literals such as `'a'` or `'\n'`.
+
+pub use self::codegen::to_whitespace;
+pub use self::disasm::disassemble;
+pub use self::error::{AsmError, AsmErrorKind};
+use crate::parser::label_map::LabelMap;
+use crate::program::{Instruction, Program};
+use crate::span::{Pos, Span};
+use crate::value::Value;
+use std::collections::HashMap;
+
+mod codegen;
+pub(crate) mod disasm;
+mod error;
+
+/// A constant representing a dummy jump target that will be resolved later
+const UNINITIALIZED_JUMP_TARGET: usize = 0;
+
+type AResult<T> = Result<T, AsmError>;
+
+/// A single word on a line of assembly along with the column and byte
+/// offset within the line it starts at
+#[derive(Copy, Clone)]
+struct Word<'a> {
+    column: usize,
+    offset: usize,
+    text: &'a str,
+}
+
+/// Splits a line of assembly into words, dropping any comment
+fn split_words(line: &str, line_no: usize) -> AResult<Vec<Word<'_>>> {
+    let mut words = vec![];
+    let mut chars = line.char_indices().enumerate().peekable();
+
+    while let Some((column, (start, c))) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c == ';' || c == '#' {
+            break;
+        }
+
+        let mut end = start + c.len_utf8();
+        if c == '\'' {
+            let mut escaped = false;
+            let mut closed = false;
+            for (_, (idx, c)) in &mut chars {
+                end = idx + c.len_utf8();
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '\'' {
+                    closed = true;
+                    break;
+                }
+            }
+
+            if !closed {
+                let kind = AsmErrorKind::InvalidCharLiteral;
+                return Err(AsmError::new(kind, line_no, column + 1));
+            }
+        } else {
+            while let Some((_, (idx, c))) = chars.peek() {
+                if c.is_whitespace() || *c == ';' || *c == '#' {
+                    break;
+                }
+                end = idx + c.len_utf8();
+                chars.next();
+            }
+        }
+
+        words.push(Word {
+            column: column + 1,
+            offset: start,
+            text: &line[start..end],
+        });
+    }
+
+    Ok(words)
+}
+
+/// Returns true if `name` is a valid label name
+fn is_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' || c == '.' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '$')
+}
+
+/// Decodes the contents of a character literal, without its quotes
+fn decode_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    let c = match chars.next()? {
+        '\\' => match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '\'' => '\'',
+            _ => return None,
+        },
+        c => c,
+    };
+
+    if chars.next().is_some() {
+        None
+    } else {
+        Some(c)
+    }
+}
+
+/// Compiles mnemonic assembly into a `Program`
+pub struct Assembler<'a> {
+    source: &'a str,
+    line_no: usize,
+    /// The byte offset of the start of the current line
+    line_start: usize,
+    /// The span of the statement on the current line
+    span: Span,
+    labels: LabelMap,
+    /// The numeric label assigned to each label name
+    label_ids: HashMap<&'a str, usize>,
+    /// The line number and label operand of every jump instruction, keyed
+    /// by instruction index
+    jump_sites: HashMap<usize, (usize, Word<'a>)>,
+    program: Program,
+}
+
+impl<'a> Assembler<'a> {
+    /// Constructs a new assembler over the given source code
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            line_no: 0,
+            line_start: 0,
+            span: Span::default(),
+            labels: LabelMap::new(),
+            label_ids: HashMap::new(),
+            jump_sites: HashMap::new(),
+            program: Program::new(),
+        }
+    }
+
+    fn error(&self, kind: AsmErrorKind, column: usize) -> AsmError {
+        AsmError::new(kind, self.line_no, column)
+    }
+
+    /// Returns the span of the given words on the current line
+    fn span_of(&self, first: Word<'a>, last: Word<'a>) -> Span {
+        let pos = |word: Word<'a>, chars: usize, bytes: usize| Pos {
+            offset: self.line_start + word.offset + bytes,
+            line: self.line_no,
+            column: word.column + chars,
+        };
+
+        let end = pos(last, last.text.chars().count(), last.text.len());
+        Span::new(pos(first, 0, 0), end)
+    }
+
+    /// Adds an instruction to the current program
+    fn emit(&mut self, inst: Instruction) {
+        self.program.emit(inst, self.span);
+    }
+
+    /// Fetches the numeric label for a label name, assigning a new one if
+    /// the name has not been seen before
+    fn label_id(&mut self, name: &'a str) -> usize {
+        if let Some(label) = self.label_ids.get(name) {
+            return *label;
+        }
+
+        let label = self.label_ids.len();
+        self.label_ids.insert(name, label);
+        self.program.set_label_name(label, name.to_string());
+        label
+    }
+
+    /// Marks the current position in the program with a label
+    fn define_label(&mut self, name: &'a str, word: Word<'a>) -> AResult<()> {
+        if !is_label_name(name) {
+            return Err(self.error(AsmErrorKind::InvalidLabelName, word.column));
+        }
+
+        let label = self.label_id(name);
+        let pc = self.program.inst_count();
+        let span = self.span_of(word, word);
+        if self.labels.add_label(label, pc, span).is_err() {
+            let kind = AsmErrorKind::DuplicateLabel(name.to_string());
+            return Err(self.error(kind, word.column));
+        }
+
+        Ok(())
+    }
+
+    /// Fetches the single operand of an instruction
+    fn operand(&self, mnemonic: Word<'a>, operands: &[Word<'a>]) -> AResult<Word<'a>> {
+        match operands {
+            [] => {
+                let column = mnemonic.column + mnemonic.text.chars().count();
+                Err(self.error(AsmErrorKind::MissingOperand, column))
+            }
+            [operand] => Ok(*operand),
+            [_, extra, ..] => Err(self.error(AsmErrorKind::UnexpectedOperand, extra.column)),
+        }
+    }
+
+    /// Reads a number operand
+    fn number(&self, mnemonic: Word<'a>, operands: &[Word<'a>]) -> AResult<Value> {
+        let operand = self.operand(mnemonic, operands)?;
+        let text = operand.text;
+
+        if text.starts_with('\'') {
+            let inner = &text[1..text.len() - 1];
+            match decode_char(inner) {
+                Some(c) => Ok(Value::from(i64::from(u32::from(c)))),
+                None => Err(self.error(AsmErrorKind::InvalidCharLiteral, operand.column)),
+            }
+        } else {
+            match text.parse() {
+                Ok(x) => Ok(x),
+                Err(_) => Err(self.error(AsmErrorKind::InvalidNumber, operand.column)),
+            }
+        }
+    }
+
+    /// Reads a non-negative number operand that must fit in an `i64`
+    fn small_number(&self, mnemonic: Word<'a>, operands: &[Word<'a>]) -> AResult<i64> {
+        let num = self.number(mnemonic, operands)?;
+        match num.to_i64() {
+            Some(x) if x < 0 => Err(self.error(AsmErrorKind::NegativeOperand, operands[0].column)),
+            Some(x) => Ok(x),
+            None => Err(self.error(AsmErrorKind::InvalidNumber, operands[0].column)),
+        }
+    }
+
+    /// Reads a label operand
+    fn label(&self, mnemonic: Word<'a>, operands: &[Word<'a>]) -> AResult<Word<'a>> {
+        let operand = self.operand(mnemonic, operands)?;
+        if is_label_name(operand.text) {
+            Ok(operand)
+        } else {
+            Err(self.error(AsmErrorKind::InvalidLabelName, operand.column))
+        }
+    }
+
+    /// Emits an instruction whose jump target will be resolved later
+    fn jump(
+        &mut self,
+        inst: Instruction,
+        mnemonic: Word<'a>,
+        operands: &[Word<'a>],
+    ) -> AResult<()> {
+        let operand = self.label(mnemonic, operands)?;
+        let label = self.label_id(operand.text);
+        let idx = self.program.inst_count();
+        self.labels.add_inst(idx, label);
+        self.jump_sites.insert(idx, (self.line_no, operand));
+
+        self.emit(inst);
+        Ok(())
+    }
+
+    /// Assembles a single instruction
+    fn statement(&mut self, words: &[Word<'a>]) -> AResult<()> {
+        let mnemonic = words[0];
+        let operands = &words[1..];
+
+        let inst = match mnemonic.text.to_ascii_lowercase().as_str() {
+            "push" => {
+                let num = self.number(mnemonic, operands)?;
+                let idx = self.program.add_const(num);
+                self.emit(Instruction::Push(idx));
+                return Ok(());
+            }
+            "copy" => {
+                let num = self.small_number(mnemonic, operands)?;
+                self.emit(Instruction::Copy(num));
+                return Ok(());
+            }
+            "slide" => {
+                let num = self.small_number(mnemonic, operands)?;
+                self.emit(Instruction::Slide(num));
+                return Ok(());
+            }
+            "label" => {
+                let operand = self.label(mnemonic, operands)?;
+                return self.define_label(operand.text, operand);
+            }
+            "call" => {
+                let inst = Instruction::Call(UNINITIALIZED_JUMP_TARGET);
+                return self.jump(inst, mnemonic, operands);
+            }
+            "jmp" | "jump" => {
+                let inst = Instruction::Jump(UNINITIALIZED_JUMP_TARGET);
+                return self.jump(inst, mnemonic, operands);
+            }
+            "jz" => {
+                let inst = Instruction::JumpIfZero(UNINITIALIZED_JUMP_TARGET);
+                return self.jump(inst, mnemonic, operands);
+            }
+            "jn" => {
+                let inst = Instruction::JumpIfNeg(UNINITIALIZED_JUMP_TARGET);
+                return self.jump(inst, mnemonic, operands);
+            }
+            "dup" => Instruction::Dup,
+            "swap" => Instruction::Swap,
+            "pop" | "drop" => Instruction::Pop,
+            "add" => Instruction::Add,
+            "sub" => Instruction::Subtract,
+            "mul" => Instruction::Multiply,
+            "div" => Instruction::Divide,
+            "mod" => Instruction::Modulo,
+            "store" => Instruction::Store,
+            "retrieve" | "load" => Instruction::Retrieve,
+            "ret" => Instruction::Return,
+            "end" => Instruction::End,
+            "outc" => Instruction::OutputChar,
+            "outn" => Instruction::OutputNum,
+            "readc" => Instruction::ReadChar,
+            "readn" => Instruction::ReadNum,
+            _ => {
+                let kind = AsmErrorKind::UnknownMnemonic(mnemonic.text.to_string());
+                return Err(self.error(kind, mnemonic.column));
+            }
+        };
+
+        if let Some(extra) = operands.first() {
+            return Err(self.error(AsmErrorKind::UnexpectedOperand, extra.column));
+        }
+
+        self.emit(inst);
+        Ok(())
+    }
+
+    /// Assembles a single line of source
+    fn line(&mut self, line: &'a str) -> AResult<()> {
+        let mut words = split_words(line, self.line_no)?;
+
+        if let Some(first) = words.first() {
+            if first.text.ends_with(':') && !first.text.starts_with('\'') {
+                let name = &first.text[..first.text.len() - 1];
+                self.define_label(name, *first)?;
+                words.remove(0);
+            }
+        }
+
+        match (words.first(), words.last()) {
+            (Some(first), Some(last)) => {
+                self.span = self.span_of(*first, *last);
+                self.statement(&words)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Assembles the input
+    pub fn assemble(mut self) -> Result<Program, AsmError> {
+        for (idx, line) in self.source.lines().enumerate() {
+            self.line_no = idx + 1;
+            self.line_start = line.as_ptr() as usize - self.source.as_ptr() as usize;
+            self.line(line)?;
+        }
+
+        if let Err(idxs) = self.labels.patch_jumps(&mut self.program) {
+            let (line_no, operand) = self.jump_sites[&idxs[0]];
+            let kind = AsmErrorKind::UndefinedLabel(operand.text.to_string());
+            return Err(AsmError::new(kind, line_no, operand.column));
+        }
+
+        Ok(self.program)
+    }
+}
+
+/// Assembles the mnemonic source code in `source` into a `Program`
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    Assembler::new(source).assemble()
+}
@@ -0,0 +1,462 @@
+//! An interactive debugger that drives a [`Vm`] one instruction at a time.
+//!
+//! Commands are read line by line from the debugger's input. An empty line
+//! repeats the previous command.
+//!
+//! | Command                 | Effect                                          |
+//! |-------------------------|-------------------------------------------------|
+//! | `step`, `s`             | Execute one instruction, entering calls         |
+//! | `next`, `n`             | Execute one instruction, stepping over calls    |
+//! | `finish`, `f`           | Run until the current subroutine returns        |
+//! | `continue`, `c`         | Run until a breakpoint or the program halts     |
+//! | `break line N`          | Break at the first instruction on line `N`      |
+//! | `break pc N`            | Break at instruction index `N`                  |
+//! | `break label L`         | Break at the instruction marked by label `L`    |
+//! | `delete N`              | Remove breakpoint number `N`                    |
+//! | `breakpoints`           | List all breakpoints                            |
+//! | `list`, `l`             | Show the instructions around the current one    |
+//! | `stack`                 | Print the operand stack                         |
+//! | `heap`                  | Print the heap                                  |
+//! | `frames`, `bt`          | Print the call stack                            |
+//! | `set stack I V`         | Set the `I`th stack value from the top to `V`   |
+//! | `set heap A V`          | Set heap address `A` to `V`                     |
+//! | `help`                  | List the available commands                     |
+//! | `quit`, `q`             | Stop debugging                                  |
+
+use crate::asm::disasm::{label_name, mnemonic};
+use crate::program::Program;
+use crate::value::Value;
+use crate::vm::{RuntimeError, Status, Vm};
+use std::cell::RefCell;
+use std::io::{self, BufRead, Read, Write};
+use std::rc::Rc;
+
+/// How many instructions `list` shows on either side of the current one
+const LIST_CONTEXT: usize = 4;
+
+const HELP: &str = "\
+step, s            execute one instruction, entering calls
+next, n            execute one instruction, stepping over calls
+finish, f          run until the current subroutine returns
+continue, c        run until a breakpoint or the program halts
+break line N       break at the first instruction on line N
+break pc N         break at instruction index N
+break label L      break at the instruction marked by label L
+delete N           remove breakpoint number N
+breakpoints        list all breakpoints
+list, l            show the instructions around the current one
+stack              print the operand stack
+heap               print the heap
+frames, bt         print the call stack
+set stack I V      set the Ith stack value from the top to V
+set heap A V       set heap address A to V
+quit, q            stop debugging";
+
+/// A reader that the debugger and the program it debugs can both read
+/// from, such as stdin. Each clone takes a whole line at a time from the
+/// underlying reader, so every line goes to whichever one asks for input
+/// first and nothing is buffered out from under the other
+pub struct SharedReader<R> {
+    inner: Rc<RefCell<R>>,
+    /// The line this handle took from the underlying reader
+    line: Vec<u8>,
+    /// How much of `line` has been consumed
+    pos: usize,
+}
+
+impl<R: BufRead> SharedReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(reader)),
+            line: vec![],
+            pos: 0,
+        }
+    }
+}
+
+impl<R> Clone for SharedReader<R> {
+    /// Returns another handle to the same underlying reader
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+            line: vec![],
+            pos: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Read for SharedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for SharedReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.line.len() {
+            self.line.clear();
+            self.pos = 0;
+            self.inner.borrow_mut().read_until(b'\n', &mut self.line)?;
+        }
+        Ok(&self.line[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.line.len());
+    }
+}
+
+/// A location the debugger stops at before executing
+struct Breakpoint {
+    pc: usize,
+    /// How the user described the location
+    description: String,
+}
+
+/// An interactive debugger for a single run of a program
+pub struct Debugger<'a> {
+    vm: Vm<'a>,
+    program: &'a Program,
+    breakpoints: Vec<Option<Breakpoint>>,
+    /// Where debugger commands are read from
+    input: Box<dyn BufRead + 'a>,
+    /// Where debugger messages are written to
+    output: Box<dyn Write + 'a>,
+    last_command: String,
+    /// The error the program halted with, if any
+    error: Option<RuntimeError>,
+}
+
+impl<'a> Debugger<'a> {
+    /// Constructs a debugger for the given VM, reading commands from `input`
+    /// and writing its messages to `output`
+    pub fn new<R, W>(vm: Vm<'a>, input: R, output: W) -> Self
+    where
+        R: BufRead + 'a,
+        W: Write + 'a,
+    {
+        let program = vm.program();
+        Self {
+            vm,
+            program,
+            breakpoints: vec![],
+            input: Box::new(input),
+            output: Box::new(output),
+            last_command: String::new(),
+            error: None,
+        }
+    }
+
+    /// Returns the VM being debugged
+    pub fn vm(&self) -> &Vm<'a> {
+        &self.vm
+    }
+
+    /// Describes the instruction at `pc`
+    fn describe(&self, pc: usize) -> String {
+        if pc < self.program.inst_count() {
+            let inst = self.program.inst_at(pc);
+            format!(
+                "{:04}  line {:<5} {}",
+                pc,
+                self.program.line_at(pc),
+                mnemonic(self.program, inst)
+            )
+        } else {
+            format!("{:04}  <end of program>", pc)
+        }
+    }
+
+    /// Prints the instruction about to be executed
+    fn print_location(&mut self) -> io::Result<()> {
+        let location = self.describe(self.vm.pc());
+        writeln!(self.output, "=> {}", location)
+    }
+
+    /// Returns the number of the breakpoint at `pc`, if any
+    fn breakpoint_at(&self, pc: usize) -> Option<usize> {
+        self.breakpoints
+            .iter()
+            .position(|x| x.as_ref().is_some_and(|x| x.pc == pc))
+    }
+
+    /// Executes instructions until `done` returns true, a breakpoint is
+    /// reached, or the program halts
+    fn run_until<F>(&mut self, done: F) -> io::Result<()>
+    where
+        F: Fn(&Vm) -> bool,
+    {
+        if self.vm.is_halted() {
+            return writeln!(self.output, "The program is not running.");
+        }
+
+        loop {
+            match self.vm.step() {
+                Status::Continue => {}
+                Status::Halted => {
+                    self.output.flush()?;
+                    return writeln!(self.output, "Program halted.");
+                }
+                Status::NeedsInput => {
+                    return writeln!(self.output, "The program is waiting for input.");
+                }
+                Status::Error(traceback) => {
+                    writeln!(self.output, "{}", traceback)?;
+                    self.error = Some(traceback.reason);
+                    return Ok(());
+                }
+            }
+
+            if done(&self.vm) {
+                break;
+            }
+            if let Some(idx) = self.breakpoint_at(self.vm.pc()) {
+                writeln!(self.output, "Breakpoint {}", idx + 1)?;
+                break;
+            }
+        }
+
+        self.print_location()
+    }
+
+    /// Resolves a breakpoint location given as a kind and an argument
+    fn resolve(&self, kind: &str, arg: &str) -> Option<usize> {
+        match kind {
+            "line" => {
+                let line_no: usize = arg.parse().ok()?;
+                (0..self.program.inst_count()).find(|pc| self.program.line_at(*pc) == line_no)
+            }
+            "pc" => arg
+                .parse()
+                .ok()
+                .filter(|pc| *pc < self.program.inst_count()),
+            "label" => {
+                let label = self.program.find_label(arg)?;
+                self.program.label_pc(label)
+            }
+            _ => None,
+        }
+    }
+
+    fn add_breakpoint(&mut self, args: &[&str]) -> io::Result<()> {
+        let pc = match args {
+            [kind, arg] => self.resolve(kind, arg),
+            _ => return writeln!(self.output, "Usage: break line|pc|label LOCATION"),
+        };
+
+        match pc {
+            Some(pc) => {
+                let description = args.join(" ");
+                writeln!(
+                    self.output,
+                    "Breakpoint {} at {}",
+                    self.breakpoints.len() + 1,
+                    self.describe(pc)
+                )?;
+                self.breakpoints.push(Some(Breakpoint { pc, description }));
+                Ok(())
+            }
+            None => writeln!(self.output, "No instruction at {}", args.join(" ")),
+        }
+    }
+
+    fn delete_breakpoint(&mut self, args: &[&str]) -> io::Result<()> {
+        let idx: Option<usize> = args.first().and_then(|x| x.parse().ok());
+        match idx.and_then(|x| self.breakpoints.get_mut(x.wrapping_sub(1))) {
+            Some(breakpoint) if breakpoint.is_some() => {
+                *breakpoint = None;
+                Ok(())
+            }
+            _ => writeln!(self.output, "No such breakpoint"),
+        }
+    }
+
+    fn list_breakpoints(&mut self) -> io::Result<()> {
+        for (idx, breakpoint) in self.breakpoints.iter().enumerate() {
+            if let Some(breakpoint) = breakpoint {
+                writeln!(
+                    self.output,
+                    "{}: {} ({})",
+                    idx + 1,
+                    breakpoint.description,
+                    self.describe(breakpoint.pc)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn list(&mut self) -> io::Result<()> {
+        let pc = self.vm.pc();
+        let start = pc.saturating_sub(LIST_CONTEXT);
+        let end = (pc + LIST_CONTEXT + 1).min(self.program.inst_count());
+
+        for idx in start..end {
+            for label in self.program.labels_at(idx) {
+                writeln!(self.output, "      {}:", label_name(self.program, label))?;
+            }
+
+            let marker = if idx == pc { "=>" } else { "  " };
+            let breakpoint = if self.breakpoint_at(idx).is_some() {
+                "*"
+            } else {
+                " "
+            };
+            writeln!(
+                self.output,
+                "{}{} {}",
+                marker,
+                breakpoint,
+                self.describe(idx)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn print_stack(&mut self) -> io::Result<()> {
+        if self.vm.stack().is_empty() {
+            return writeln!(self.output, "The stack is empty.");
+        }
+
+        for (idx, value) in self.vm.stack().iter().rev().enumerate() {
+            writeln!(self.output, "[{}] {}", idx, value)?;
+        }
+
+        Ok(())
+    }
+
+    fn print_heap(&mut self) -> io::Result<()> {
+        if self.vm.heap().is_empty() {
+            return writeln!(self.output, "The heap is empty.");
+        }
+
+        let mut entries: Vec<_> = self.vm.heap().iter().collect();
+        entries.sort();
+        for (addr, value) in entries {
+            writeln!(self.output, "{}: {}", addr, value)?;
+        }
+
+        Ok(())
+    }
+
+    fn print_frames(&mut self) -> io::Result<()> {
+        let depth = self.vm.call_stack().len();
+        for (idx, frame) in self.vm.call_stack().iter().rev().enumerate() {
+            // Every frame but the innermost has already moved past its call
+            let pc = if idx == 0 { frame.pc } else { frame.pc - 1 };
+            let name = match frame.label {
+                Some(label) => label_name(self.program, label),
+                None => "main".to_string(),
+            };
+
+            let line = if pc < self.program.inst_count() {
+                format!("line {}", self.program.line_at(pc))
+            } else {
+                "end of program".to_string()
+            };
+            writeln!(self.output, "#{} {} at {}", depth - 1 - idx, name, line)?;
+        }
+
+        Ok(())
+    }
+
+    fn set(&mut self, args: &[&str]) -> io::Result<()> {
+        let (target, location, value) = match args {
+            [target, location, value] => (*target, *location, *value),
+            _ => return writeln!(self.output, "Usage: set stack|heap LOCATION VALUE"),
+        };
+
+        let value: Value = match value.parse() {
+            Ok(x) => x,
+            Err(_) => return writeln!(self.output, "Invalid value '{}'", value),
+        };
+
+        match target {
+            "stack" => {
+                let len = self.vm.stack().len();
+                let idx: Option<usize> = location.parse().ok().filter(|x| *x < len);
+                match idx {
+                    Some(idx) => self.vm.stack_mut()[len - 1 - idx] = value,
+                    None => return writeln!(self.output, "No stack entry {}", location),
+                }
+            }
+            "heap" => match location.parse() {
+                Ok(addr) => {
+                    self.vm.heap_mut().insert(addr, value);
+                }
+                Err(_) => return writeln!(self.output, "Invalid address '{}'", location),
+            },
+            _ => return writeln!(self.output, "Usage: set stack|heap LOCATION VALUE"),
+        }
+
+        Ok(())
+    }
+
+    /// Executes a single command, returning false if the user asked to quit
+    fn command(&mut self, line: &str) -> io::Result<bool> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok(true),
+        };
+
+        match command {
+            "step" | "s" => self.run_until(|_| true)?,
+            "next" | "n" => {
+                let depth = self.vm.call_stack().len();
+                self.run_until(|vm| vm.call_stack().len() <= depth)?;
+            }
+            "finish" | "f" => {
+                let depth = self.vm.call_stack().len();
+                self.run_until(|vm| vm.call_stack().len() < depth)?;
+            }
+            "continue" | "c" => self.run_until(|_| false)?,
+            "break" | "b" => self.add_breakpoint(args)?,
+            "delete" | "d" => self.delete_breakpoint(args)?,
+            "breakpoints" => self.list_breakpoints()?,
+            "list" | "l" => self.list()?,
+            "stack" => self.print_stack()?,
+            "heap" => self.print_heap()?,
+            "frames" | "bt" => self.print_frames()?,
+            "set" => self.set(args)?,
+            "help" | "h" => writeln!(self.output, "{}", HELP)?,
+            "quit" | "q" => return Ok(false),
+            _ => writeln!(self.output, "Unknown command '{}'. Try 'help'.", command)?,
+        }
+
+        Ok(true)
+    }
+
+    /// Runs the debugger until the user quits or its input ends. Returns
+    /// the runtime error the program halted with, if any
+    pub fn run(mut self) -> io::Result<Option<RuntimeError>> {
+        self.print_location()?;
+
+        loop {
+            write!(self.output, "(wsdb) ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(self.error);
+            }
+
+            let line = if line.trim().is_empty() {
+                self.last_command.clone()
+            } else {
+                line.trim().to_string()
+            };
+
+            if !self.command(&line)? {
+                return Ok(self.error);
+            }
+            self.last_command = line;
+        }
+    }
+}
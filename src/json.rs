@@ -0,0 +1,95 @@
+//! A minimal JSON writer for machine readable diagnostics
+
+use crate::span::{Pos, Span};
+use std::fmt::{Display, Write};
+
+/// Quotes and escapes a string as a JSON string literal
+pub fn string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Joins already encoded JSON values into an array
+pub fn array<I: IntoIterator<Item = String>>(values: I) -> String {
+    let values: Vec<_> = values.into_iter().collect();
+    format!("[{}]", values.join(","))
+}
+
+/// Builds a JSON object one field at a time, keeping the fields in the
+/// order they were added
+pub struct Object {
+    out: String,
+}
+
+impl Object {
+    pub fn new() -> Self {
+        Self {
+            out: String::from("{"),
+        }
+    }
+
+    /// Adds a field whose value is already encoded as JSON
+    pub fn raw(mut self, key: &str, value: &str) -> Self {
+        if self.out.len() > 1 {
+            self.out.push(',');
+        }
+        write!(self.out, "{}:{}", string(key), value).unwrap();
+        self
+    }
+
+    pub fn string(self, key: &str, value: &str) -> Self {
+        self.raw(key, &string(value))
+    }
+
+    pub fn number<N: Display>(self, key: &str, value: N) -> Self {
+        self.raw(key, &value.to_string())
+    }
+
+    /// Adds a number field, or `null` if there is no value
+    pub fn optional<N: Display>(self, key: &str, value: Option<N>) -> Self {
+        match value {
+            Some(value) => self.number(key, value),
+            None => self.raw(key, "null"),
+        }
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push('}');
+        self.out
+    }
+}
+
+impl Default for Object {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn pos(pos: Pos) -> String {
+    Object::new()
+        .number("offset", pos.offset)
+        .number("line", pos.line)
+        .number("column", pos.column)
+        .finish()
+}
+
+/// Encodes a span of source as an object with `start` and `end` positions
+pub fn span(span: Span) -> String {
+    Object::new()
+        .raw("start", &pos(span.start))
+        .raw("end", &pos(span.end))
+        .finish()
+}
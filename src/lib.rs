@@ -12,34 +12,96 @@
 //! ## Limitations
 //! There are several limitations that prevent this implementation from
 //! being fully compliant with the reference implementation in Haskell.
-//! The most notable is all integers are restricted in size to standard 64-bit
-//! signed integers instead of arbitrary precision integers. This was done
-//! mostly for simplicity's sake.
+//! The most notable is by default all integers are restricted in size to
+//! standard 64-bit signed integers instead of arbitrary precision integers.
+//! This was done mostly for simplicity's sake.
+//!
+//! Enabling the `bignum` cargo feature switches every [`Value`] on the stack,
+//! on the heap and in the constant pool to an arbitrary precision integer.
+//! In this mode division and modulo also round towards negative infinity,
+//! like Haskell's `div` and `mod`.
 //!
 //! The stack traceback probably has some runtime cost associated with managing
 //! the virtual call stack.
 //!
+//! ## Embedding
+//! Besides the convenience functions [`run_source`] and [`run_file`], the
+//! interpreter can be driven directly: [`parse`] compiles source code into a
+//! [`Program`], which is then executed by a [`Vm`]. Errors are reported as
+//! values rather than printed. [`Parser::parse_all`] recovers from errors
+//! and returns every error and warning along with the partial program.
+//!
+//! [`Vm::with_io`] lets the program read from and write to any
+//! `BufRead`/`Write` pair instead of stdin and stdout, and
+//! [`Vm::with_config`] adjusts how the program is run, such as the
+//! [`OverflowPolicy`] applied to arithmetic.
+//!
+//! Programs can also be built from a mnemonic assembly format with the
+//! [`asm`] module, which can translate them back into Whitespace source or
+//! disassemble any `Program` into annotated mnemonics.
+//!
+//! [`Vm::step`] and [`Vm::run_for`] execute a bounded number of instructions
+//! and report a [`Status`], so a host can pause the program, inspect its
+//! stack, heap and call frames, and resume it later. A reader that returns
+//! `io::ErrorKind::WouldBlock` makes the VM report [`Status::NeedsInput`]
+//! instead of failing. The interactive [`debugger`] is built on this API.
+//! [`Vm::with_tracer`] logs every executed instruction, optionally only
+//! those on certain lines or in certain subroutines, as text or JSON Lines.
+//! [`Vm::with_profile`] counts how often each instruction runs and how much
+//! time and how many instructions each subroutine takes in a [`Profile`],
+//! which can also be exported as folded call stacks for flame graphs or as
+//! a Chrome trace.
+//!
+//! The [`testing`] module runs programs against input and expected output
+//! fixtures, and reports the results for people or CI systems.
+//!
+//! ```
+//! let program = whitespace_vm::parse("   \t\n\t\n \t\n\n\n").unwrap();
+//! match whitespace_vm::Vm::new(&program).run() {
+//!     Ok(outcome) => println!("final stack: {:?}", outcome.stack),
+//!     Err(traceback) => eprintln!("{}", traceback),
+//! }
+//! ```
+//!
 //! [1]: https://en.wikipedia.org/wiki/Whitespace_(programming_language)
 
-use crate::parser::Parser;
-use crate::vm::Vm;
+pub use crate::parser::{
+    ErrorKind, InstType, ParseError, ParseOutput, Parser, Warning, WarningKind,
+};
+pub use crate::program::{Instruction, Program};
+pub use crate::span::{Pos, Span};
+pub use crate::value::{ParseValueError, Value};
+pub use crate::vm::{
+    CallFrame, Config, EofPolicy, Outcome, OverflowPolicy, Profile, RuntimeError, Status,
+    SubroutineStats, TraceEntry, TraceFilter, TraceFormat, Traceback, Tracer, Vm, VmResult,
+};
 use std::fs::OpenOptions;
 use std::io::Read;
 use std::path::Path;
 
-mod parser;
-mod program;
+pub mod asm;
+pub mod debugger;
+pub mod json;
+pub mod parser;
+pub mod program;
+mod span;
+pub mod testing;
 mod token;
-mod vm;
+mod value;
+pub mod vm;
+
+/// Compiles the Whitespace source code in `source` into a `Program`
+pub fn parse(source: &str) -> Result<Program, ParseError> {
+    Parser::new(source).parse()
+}
 
 /// Runs a given Whitespace program where the source code is
 /// represented by `source`
 pub fn run_source(source: &str) {
-    let parser = Parser::new(source);
-    let program = match parser.parse() {
+    let program = match parse(source) {
         Ok(x) => x,
         Err(error) => {
-            error.print_error();
+            error.print_error(source);
             return;
         }
     };
@@ -57,14 +119,15 @@ pub fn run_file<P: AsRef<Path>>(filename: P) {
     let mut file = match OpenOptions::new().read(true).open(filename) {
         Ok(x) => x,
         Err(_) => {
-            println!("Could not open '{}'", err_msg);
+            eprintln!("Could not open '{}'", err_msg);
             return;
         }
     };
 
     let mut source = String::new();
     if file.read_to_string(&mut source).is_err() {
-        println!("Error reading file");
+        eprintln!("Error reading file");
+        return;
     }
 
     run_source(&source);
@@ -1,11 +1,639 @@
 use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::ops::RangeInclusive;
+use std::process;
+use std::str::FromStr;
+use whitespace_vm::debugger::{Debugger, SharedReader};
+use whitespace_vm::json::Object;
+use whitespace_vm::testing::{self, report};
+use whitespace_vm::{
+    Config, EofPolicy, OverflowPolicy, Parser, Profile, Program, RuntimeError, TraceFilter,
+    TraceFormat, Tracer, Vm,
+};
 
-fn main() {
-    let args: Vec<_> = env::args().collect();
-    if args.len() == 1 {
-        println!("Usage: wspace.exe [file]");
+const USAGE: &str = "\
+Usage: wspace [run] [options] FILE
+       wspace COMMAND [options] FILE
+       wspace test [options] [DIR|FILE]
+
+Commands:
+    run       Run a program (the default)
+    check     Parse a program and report errors and warnings
+    disasm    Print a program as annotated mnemonics
+    asm       Assemble mnemonics into Whitespace
+    fmt       Print a program in canonical form, with comments removed
+    debug     Run a program under the interactive debugger
+    trace     Run a program, logging every instruction (run --trace=human)
+    profile   Run a program and print a profile (run --profile)
+    test      Run every NAME.ws under DIR (default '.') that has a NAME.out
+              or NAME.err, with NAME.in as its input
+
+FILE may be '-' to read the source from stdin.
+
+Options:
+    --input=FILE                    Read the program's input from FILE
+    --eof=error|0|-1                What reading past the end of input does
+    --overflow=trap|wrap|saturate   What arithmetic overflow does
+    --fuel=N                        Stop after executing N instructions
+                                    (100000000 by default for tests)
+    --max-stack=N                   Limit the operand stack to N values
+    --max-call-depth=N              Limit the call stack to N frames
+    --max-heap=N                    Limit the heap to N addresses
+    --implicit-end                  Halt normally when running off the end
+    --warnings                      Print parser warnings, which only
+                                    'check' does by default
+    --no-warnings                   Do not print parser warnings
+    --error-format=human|json       How errors and warnings are printed
+    --trace=human|json              Log every executed instruction to stderr
+    --trace-file=FILE               Write the trace to FILE instead
+    --trace-lines=N[-M]             Only trace instructions on lines N to M
+    --trace-label=LABEL             Only trace instructions run under the
+                                    subroutine LABEL, which may be repeated
+    --profile                       Print a profile of the hottest lines,
+                                    instructions and subroutines to stderr
+    --profile-folded=FILE           Write instruction counts per call stack
+                                    to FILE in the folded flame graph format
+    --profile-chrome=FILE           Write a timeline of subroutine calls to
+                                    FILE in the Chrome trace event format
+    --jobs=N                        Run N tests at once
+    --report=human|tap|junit        How test results are printed
+    -h, --help                      Print this message
+    -V, --version                   Print the version";
+
+/// The process exit codes. Each runtime error has its own code so scripts
+/// can tell why a program failed
+mod exit {
+    pub const SUCCESS: i32 = 0;
+    /// At least one test failed
+    pub const TEST_FAILED: i32 = 1;
+    /// The command line arguments were invalid
+    pub const USAGE: i32 = 2;
+    /// The source file could not be read
+    pub const NO_INPUT: i32 = 3;
+    /// The source file could not be parsed or assembled
+    pub const PARSE: i32 = 4;
+    /// A trace or profile could not be written
+    pub const OUTPUT: i32 = 5;
+}
+
+/// Returns the exit code for a program that halted with the given error
+fn runtime_exit_code(error: &RuntimeError) -> i32 {
+    match error {
+        RuntimeError::ZeroDivision => 10,
+        RuntimeError::InvalidHeapEntry(_) => 11,
+        RuntimeError::IoError => 12,
+        RuntimeError::NumParseError => 13,
+        RuntimeError::StackUnderflow => 14,
+        RuntimeError::Overflow => 15,
+        RuntimeError::FuelExhausted => 16,
+        RuntimeError::StackOverflow => 17,
+        RuntimeError::CallStackOverflow => 18,
+        RuntimeError::HeapLimitExceeded => 19,
+        RuntimeError::MissingEnd => 20,
+        RuntimeError::UnresolvedCall => 21,
+    }
+}
+
+/// What the interpreter was asked to do
+#[derive(Copy, Clone, PartialEq)]
+enum Command {
+    Run,
+    Check,
+    Disasm,
+    Asm,
+    Fmt,
+    Debug,
+    Test,
+}
+
+/// How the results of `wspace test` are printed
+#[derive(Copy, Clone, PartialEq)]
+enum ReportFormat {
+    Human,
+    Tap,
+    Junit,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "run" => Some(Command::Run),
+            "check" => Some(Command::Check),
+            "disasm" => Some(Command::Disasm),
+            "asm" => Some(Command::Asm),
+            "fmt" => Some(Command::Fmt),
+            "debug" => Some(Command::Debug),
+            "test" => Some(Command::Test),
+            // Shorthands for `run --trace=human` and `run --profile`
+            "trace" | "profile" => Some(Command::Run),
+            _ => None,
+        }
+    }
+}
+
+/// The parsed command line
+struct Options {
+    command: Command,
+    filename: String,
+    /// Where the program's input comes from, or `None` for stdin
+    input: Option<String>,
+    config: Config,
+    /// Whether parser warnings are printed, which by default only `check`
+    /// does
+    show_warnings: bool,
+    json: bool,
+    /// How executed instructions are logged, or `None` to not log them
+    trace: Option<TraceFormat>,
+    /// Where the trace is written, or `None` for stderr
+    trace_file: Option<String>,
+    trace_lines: Option<RangeInclusive<usize>>,
+    /// The names of the subroutines whose instructions are traced
+    trace_labels: Vec<String>,
+    profile: bool,
+    /// Where instruction counts per call stack are written, if anywhere
+    profile_folded: Option<String>,
+    /// Where the timeline of subroutine calls is written, if anywhere
+    profile_chrome: Option<String>,
+    /// How many tests run at once
+    jobs: usize,
+    report: ReportFormat,
+}
+
+/// Prints the usage message and exits with a usage error
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(exit::USAGE);
+}
+
+/// Prints an error about the command line and exits with a usage error
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!("Try 'wspace --help' for more information.");
+    process::exit(exit::USAGE);
+}
+
+fn parse_overflow(policy: &str) -> Option<OverflowPolicy> {
+    match policy {
+        "trap" => Some(OverflowPolicy::Trap),
+        "wrap" => Some(OverflowPolicy::Wrap),
+        "saturate" => Some(OverflowPolicy::Saturate),
+        _ => None,
+    }
+}
+
+fn parse_eof(policy: &str) -> Option<EofPolicy> {
+    match policy {
+        "error" => Some(EofPolicy::Error),
+        "0" => Some(EofPolicy::Zero),
+        "-1" => Some(EofPolicy::MinusOne),
+        _ => None,
+    }
+}
+
+/// Parses the value of a numeric option, exiting if it is invalid
+fn parse_limit<T: FromStr>(option: &str, value: &str) -> T {
+    match value.parse() {
+        Ok(x) => x,
+        Err(_) => usage_error(&format!("Invalid value '{}' for {}", value, option)),
+    }
+}
+
+/// Parses the command line arguments, exiting on `--help`, `--version` or
+/// invalid arguments
+fn parse_args(args: &[String]) -> Options {
+    let mut args = args;
+    let name = args.first().map(String::as_str);
+    let command = match name.and_then(Command::from_name) {
+        Some(command) => {
+            args = &args[1..];
+            command
+        }
+        None => Command::Run,
+    };
+
+    let mut options = Options {
+        command,
+        filename: String::new(),
+        input: None,
+        config: Config::default(),
+        show_warnings: command == Command::Check,
+        json: false,
+        trace: None,
+        trace_file: None,
+        trace_lines: None,
+        trace_labels: vec![],
+        profile: false,
+        profile_folded: None,
+        profile_chrome: None,
+        jobs: testing::default_jobs(),
+        report: ReportFormat::Human,
+    };
+    match name {
+        Some("trace") => options.trace = Some(TraceFormat::Human),
+        Some("profile") => options.profile = true,
+        _ => (),
+    }
+    let mut filename = None;
+
+    for arg in args {
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            process::exit(exit::SUCCESS);
+        } else if arg == "-V" || arg == "--version" {
+            println!("wspace {}", env!("CARGO_PKG_VERSION"));
+            process::exit(exit::SUCCESS);
+        } else if let Some(input) = arg.strip_prefix("--input=") {
+            options.input = Some(input.to_string());
+        } else if let Some(policy) = arg.strip_prefix("--eof=") {
+            options.config.eof = match parse_eof(policy) {
+                Some(x) => x,
+                None => usage_error(&format!("Unknown EOF policy '{}'", policy)),
+            };
+        } else if let Some(policy) = arg.strip_prefix("--overflow=") {
+            options.config.overflow = match parse_overflow(policy) {
+                Some(x) => x,
+                None => usage_error(&format!("Unknown overflow policy '{}'", policy)),
+            };
+        } else if let Some(fuel) = arg.strip_prefix("--fuel=") {
+            options.config.fuel = Some(parse_limit("--fuel", fuel));
+        } else if let Some(max) = arg.strip_prefix("--max-stack=") {
+            options.config.max_stack = Some(parse_limit("--max-stack", max));
+        } else if let Some(max) = arg.strip_prefix("--max-call-depth=") {
+            options.config.max_call_depth = Some(parse_limit("--max-call-depth", max));
+        } else if let Some(max) = arg.strip_prefix("--max-heap=") {
+            options.config.max_heap = Some(parse_limit("--max-heap", max));
+        } else if arg == "--implicit-end" {
+            options.config.implicit_end = true;
+        } else if arg == "--warnings" {
+            options.show_warnings = true;
+        } else if arg == "--no-warnings" {
+            options.show_warnings = false;
+        } else if let Some(format) = arg.strip_prefix("--error-format=") {
+            options.json = match format {
+                "human" => false,
+                "json" => true,
+                _ => usage_error(&format!("Unknown error format '{}'", format)),
+            };
+        } else if let Some(format) = arg.strip_prefix("--trace=") {
+            options.trace = match format {
+                "human" => Some(TraceFormat::Human),
+                "json" => Some(TraceFormat::Json),
+                _ => usage_error(&format!("Unknown trace format '{}'", format)),
+            };
+        } else if let Some(filename) = arg.strip_prefix("--trace-file=") {
+            options.trace_file = Some(filename.to_string());
+        } else if let Some(lines) = arg.strip_prefix("--trace-lines=") {
+            options.trace_lines = Some(match lines.split_once('-') {
+                Some((start, end)) => {
+                    let range =
+                        parse_limit("--trace-lines", start)..=parse_limit("--trace-lines", end);
+                    if range.is_empty() {
+                        usage_error(&format!("Invalid value '{}' for --trace-lines", lines));
+                    }
+                    range
+                }
+                None => {
+                    let line = parse_limit("--trace-lines", lines);
+                    line..=line
+                }
+            });
+        } else if let Some(label) = arg.strip_prefix("--trace-label=") {
+            options.trace_labels.push(label.to_string());
+        } else if arg == "--profile" {
+            options.profile = true;
+        } else if let Some(filename) = arg.strip_prefix("--profile-folded=") {
+            options.profile_folded = Some(filename.to_string());
+        } else if let Some(filename) = arg.strip_prefix("--profile-chrome=") {
+            options.profile_chrome = Some(filename.to_string());
+        } else if let Some(jobs) = arg.strip_prefix("--jobs=") {
+            options.jobs = parse_limit("--jobs", jobs);
+        } else if let Some(format) = arg.strip_prefix("--report=") {
+            options.report = match format {
+                "human" => ReportFormat::Human,
+                "tap" => ReportFormat::Tap,
+                "junit" => ReportFormat::Junit,
+                _ => usage_error(&format!("Unknown report format '{}'", format)),
+            };
+        } else if arg.starts_with('-') && arg != "-" {
+            usage_error(&format!("Unknown option '{}'", arg));
+        } else if filename.is_none() {
+            filename = Some(arg.clone());
+        } else {
+            usage_error(&format!("Unexpected argument '{}'", arg));
+        }
+    }
+
+    options.filename = match filename {
+        Some(x) => x,
+        None if command == Command::Test => ".".to_string(),
+        None => usage(),
+    };
+    options
+}
+
+/// Prints an error about a file the interpreter itself reads or writes, as
+/// JSON if asked for
+fn file_error(action: &str, filename: &str, options: &Options) {
+    let message = format!("{} '{}'", action, filename);
+    if options.json {
+        let error = Object::new()
+            .string("type", "io_error")
+            .string("message", &message)
+            .string("path", filename)
+            .finish();
+        eprintln!("{}", error);
+    } else {
+        eprintln!("{}", message);
+    }
+}
+
+/// Reads the source file, or stdin if the file name is `-`, exiting if it
+/// cannot be read
+fn read_source(filename: &str, options: &Options) -> String {
+    let source = if filename == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source).map(|_| source)
+    } else {
+        fs::read_to_string(filename)
+    };
+
+    match source {
+        Ok(x) => x,
+        Err(_) => {
+            file_error("Could not open", filename, options);
+            process::exit(exit::NO_INPUT);
+        }
+    }
+}
+
+/// Opens the file the program reads its input from, exiting if it cannot
+/// be opened
+fn open_input(filename: &str, options: &Options) -> BufReader<File> {
+    match File::open(filename) {
+        Ok(x) => BufReader::new(x),
+        Err(_) => {
+            file_error("Could not open", filename, options);
+            process::exit(exit::NO_INPUT);
+        }
+    }
+}
+
+/// Parses the source, printing any warnings and errors. Exits if there are
+/// any errors
+fn parse(source: &str, options: &Options) -> Program {
+    let output = Parser::new(source).parse_all();
+
+    if options.show_warnings {
+        for warning in &output.warnings {
+            if options.json {
+                eprintln!("{}", warning.to_json());
+            } else {
+                eprintln!("{}", warning.render(source));
+            }
+        }
+    }
+    if !output.errors.is_empty() {
+        for error in &output.errors {
+            if options.json {
+                eprintln!("{}", error.to_json());
+            } else {
+                error.print_error(source);
+            }
+        }
+        process::exit(exit::PARSE);
+    }
+
+    output.program
+}
+
+/// Where the trace is written. Remembers the first error writing it, so a
+/// trace that cannot be written is reported as such once the program halts
+struct TraceOutput {
+    output: Box<dyn Write>,
+    error: Option<io::Error>,
+}
+
+impl TraceOutput {
+    /// Opens the trace output asked for on the command line, exiting if its
+    /// file cannot be created
+    fn open(options: &Options) -> Self {
+        let output: Box<dyn Write> = match &options.trace_file {
+            Some(filename) => match File::create(filename) {
+                Ok(x) => Box::new(BufWriter::new(x)),
+                Err(_) => {
+                    file_error("Could not create", filename, options);
+                    process::exit(exit::OUTPUT);
+                }
+            },
+            None => Box::new(io::stderr()),
+        };
+        Self {
+            output,
+            error: None,
+        }
+    }
+
+    /// Keeps the first error and hands its kind back to the tracer
+    fn fail<T>(&mut self, error: io::Error) -> io::Result<T> {
+        let kind = error.kind();
+        self.error.get_or_insert(error);
+        Err(kind.into())
+    }
+}
+
+impl Write for TraceOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.output.write(buf) {
+            Ok(x) => Ok(x),
+            Err(error) => self.fail(error),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.output.flush() {
+            Ok(()) => Ok(()),
+            Err(error) => self.fail(error),
+        }
+    }
+}
+
+/// Builds the tracer asked for on the command line, exiting if it names a
+/// label the program does not have
+fn tracer<'a>(program: &Program, options: &Options, output: &'a mut TraceOutput) -> Tracer<'a> {
+    let mut labels = vec![];
+    for name in &options.trace_labels {
+        match program.find_label(name) {
+            Some(label) => labels.push(label),
+            None => usage_error(&format!("Unknown label '{}'", name)),
+        }
+    }
+
+    let filter = TraceFilter {
+        lines: options.trace_lines.clone(),
+        labels,
+    };
+    Tracer::new(output, options.trace.unwrap()).with_filter(filter)
+}
+
+/// Runs the program, returning the exit code
+fn run(program: &Program, options: &Options) -> i32 {
+    let input: Box<dyn BufRead> = match &options.input {
+        Some(filename) => Box::new(open_input(filename, options)),
+        None => Box::new(io::stdin().lock()),
+    };
+
+    let mut trace_output = options.trace.map(|_| TraceOutput::open(options));
+    let mut profile = Profile::new();
+    if options.profile_chrome.is_some() {
+        profile = profile.with_timeline();
+    }
+    let profiling =
+        options.profile || options.profile_folded.is_some() || options.profile_chrome.is_some();
+    let mut vm = Vm::with_io(program, input, io::stdout()).with_config(options.config.clone());
+    if let Some(output) = &mut trace_output {
+        vm = vm.with_tracer(tracer(program, options, output));
+    }
+    if profiling {
+        vm = vm.with_profile(&mut profile);
+    }
+
+    let mut code = match vm.run() {
+        Ok(_) => exit::SUCCESS,
+        Err(traceback) => {
+            if options.json {
+                eprintln!("{}", traceback.to_json());
+            } else {
+                traceback.dump();
+            }
+            runtime_exit_code(&traceback.reason)
+        }
+    };
+
+    if let Some(mut output) = trace_output {
+        let _ = output.flush();
+        if let Some(error) = output.error {
+            eprintln!("Could not write the trace: {}", error);
+            code = exit::OUTPUT;
+        }
+    }
+    if options.profile {
+        eprintln!("{}", profile.report(program));
+    }
+    if let Some(filename) = &options.profile_folded {
+        write_file(filename, &profile.folded(program), options);
+    }
+    if let Some(filename) = &options.profile_chrome {
+        write_file(filename, &profile.chrome_trace(program).unwrap(), options);
+    }
+    code
+}
+
+/// Writes `contents` to a file followed by a newline, exiting if it cannot
+/// be written
+fn write_file(filename: &str, contents: &str, options: &Options) {
+    if fs::write(filename, format!("{}\n", contents)).is_err() {
+        file_error("Could not create", filename, options);
+        process::exit(exit::OUTPUT);
+    }
+}
+
+/// Runs the program under the interactive debugger, returning the exit code
+fn debug(program: &Program, options: &Options) -> i32 {
+    // Unless the program reads from a file, it shares stdin with the
+    // debugger, which hands out one line at a time to whichever asks first
+    let commands = SharedReader::new(io::stdin().lock());
+    let input: Box<dyn BufRead> = match &options.input {
+        Some(filename) => Box::new(open_input(filename, options)),
+        None => Box::new(commands.clone()),
+    };
+
+    let vm = Vm::with_io(program, input, io::stdout()).with_config(options.config.clone());
+    let debugger = Debugger::new(vm, commands, io::stdout());
+    match debugger.run() {
+        Ok(None) => exit::SUCCESS,
+        Ok(Some(error)) => runtime_exit_code(&error),
+        Err(_) => {
+            eprintln!("Error: {}", RuntimeError::IoError);
+            runtime_exit_code(&RuntimeError::IoError)
+        }
+    }
+}
+
+/// Runs every test case found at the given path, returning the exit code
+fn test(options: &Options) -> i32 {
+    let cases = match testing::discover(&options.filename) {
+        Ok(x) if !x.is_empty() => x,
+        Ok(_) => {
+            eprintln!("No tests found in '{}'", options.filename);
+            return exit::NO_INPUT;
+        }
+        Err(_) => {
+            file_error("Could not open", &options.filename, options);
+            return exit::NO_INPUT;
+        }
+    };
+
+    let mut config = options.config.clone();
+    config.fuel = config.fuel.or(Some(testing::DEFAULT_FUEL));
+
+    let results = testing::run_all(&cases, &config, options.jobs);
+    let report = match options.report {
+        ReportFormat::Human => report::human(&results),
+        ReportFormat::Tap => report::tap(&results),
+        ReportFormat::Junit => report::junit(&results),
+    };
+    println!("{}", report);
+
+    if results.iter().all(|x| x.passed()) {
+        exit::SUCCESS
     } else {
-        let filename = &args[1];
-        whitespace_vm::run_file(filename);
+        exit::TEST_FAILED
+    }
+}
+
+fn main() {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let options = parse_args(&args);
+    if options.command == Command::Test {
+        process::exit(test(&options));
     }
+
+    let source = read_source(&options.filename, &options);
+
+    let code = match options.command {
+        Command::Asm => match whitespace_vm::asm::assemble(&source) {
+            Ok(program) => {
+                print!("{}", whitespace_vm::asm::to_whitespace(&program));
+                exit::SUCCESS
+            }
+            Err(error) => {
+                if options.json {
+                    eprintln!("{}", error.to_json());
+                } else {
+                    eprintln!("{}", error);
+                }
+                exit::PARSE
+            }
+        },
+        Command::Check => {
+            parse(&source, &options);
+            exit::SUCCESS
+        }
+        Command::Disasm => {
+            let program = parse(&source, &options);
+            print!("{}", whitespace_vm::asm::disassemble(&program));
+            exit::SUCCESS
+        }
+        Command::Fmt => {
+            let program = parse(&source, &options);
+            print!("{}", whitespace_vm::asm::to_whitespace(&program));
+            exit::SUCCESS
+        }
+        Command::Run => run(&parse(&source, &options), &options),
+        Command::Debug => debug(&parse(&source, &options), &options),
+        Command::Test => unreachable!(),
+    };
+
+    process::exit(code);
 }
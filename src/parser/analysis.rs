@@ -0,0 +1,112 @@
+use super::error::{ErrorKind, ParseError};
+use super::warning::{Warning, WarningKind};
+use crate::program::{Instruction, Program};
+use crate::span::Span;
+
+/// Returns the program counters execution can continue at after the
+/// instruction at `pc`, assuming every call returns. Calls are only
+/// followed into the subroutine if `follow_calls` is set
+fn successors(program: &Program, pc: usize, follow_calls: bool) -> Vec<usize> {
+    match program.inst_at(pc) {
+        Instruction::End | Instruction::Return => vec![],
+        Instruction::Jump(target) => vec![*target],
+        Instruction::JumpIfZero(target) | Instruction::JumpIfNeg(target) => {
+            vec![*target, pc + 1]
+        }
+        Instruction::Call(target) if follow_calls => vec![*target, pc + 1],
+        _ => vec![pc + 1],
+    }
+}
+
+/// Finds every instruction reachable from `entry`. The returned vector has
+/// one extra slot for the end of the program
+pub fn reachable(program: &Program, entry: usize, follow_calls: bool) -> Vec<bool> {
+    let count = program.inst_count();
+    let mut seen = vec![false; count + 1];
+    let mut pending = vec![entry];
+
+    while let Some(pc) = pending.pop() {
+        if seen[pc] {
+            continue;
+        }
+        seen[pc] = true;
+
+        if pc < count {
+            pending.extend(successors(program, pc, follow_calls));
+        }
+    }
+
+    seen
+}
+
+/// Warns if the last instruction is reachable and lets execution continue
+/// past the end of the program. Jumps past the end are reported separately
+/// by `check_jumps`
+pub fn check_fall_through(program: &Program, warnings: &mut Vec<Warning>) {
+    let count = program.inst_count();
+    if count == 0 {
+        warnings.push(Warning::new(WarningKind::FallsOffEnd, Span::default()));
+        return;
+    }
+
+    let last = count - 1;
+    let seen = reachable(program, 0, true);
+    if seen[last] && successors(program, last, true).contains(&(last + 1)) {
+        let span = program.span_at(last);
+        warnings.push(Warning::new(WarningKind::FallsOffEnd, span));
+    }
+}
+
+/// Rejects `Copy` and `Slide` instructions with negative operands
+pub fn check_operands(program: &Program, errors: &mut Vec<ParseError>) {
+    for (pc, inst) in program.instructions().iter().enumerate() {
+        match inst {
+            Instruction::Copy(n) | Instruction::Slide(n) if *n < 0 => {
+                let span = program.span_at(pc);
+                errors.push(ParseError::new(ErrorKind::NegativeOperand, span));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Warns about every `Return` that can be reached from the main program
+/// without going through a call
+pub fn check_returns(program: &Program, warnings: &mut Vec<Warning>) {
+    let count = program.inst_count();
+    let seen = reachable(program, 0, false);
+
+    for (pc, _) in seen[..count].iter().enumerate().filter(|(_, x)| **x) {
+        if let Instruction::Return = program.inst_at(pc) {
+            let span = program.span_at(pc);
+            warnings.push(Warning::new(WarningKind::ReturnFromMain, span));
+        }
+    }
+}
+
+/// Rejects every jump or call that lands outside any subroutine body.
+///
+/// Whitespace has no syntax for where a subroutine ends, so its body is the
+/// code reachable from its label without following calls, and main's body
+/// is the code reachable from the first instruction. Every instruction a
+/// reachable jump targets is part of some body by that definition, so the
+/// only place a jump can land outside all of them is a label marked after
+/// the last instruction
+pub fn check_jumps(program: &Program, errors: &mut Vec<ParseError>) {
+    let count = program.inst_count();
+
+    for (pc, inst) in program.instructions().iter().enumerate() {
+        match inst {
+            Instruction::Call(target)
+            | Instruction::Jump(target)
+            | Instruction::JumpIfZero(target)
+            | Instruction::JumpIfNeg(target)
+                if *target == count =>
+            {
+                let span = program.span_at(pc);
+                errors.push(ParseError::new(ErrorKind::JumpPastEnd, span));
+            }
+            _ => {}
+        }
+    }
+}
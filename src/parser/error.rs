@@ -1,5 +1,11 @@
+use super::{opcode, render};
+use crate::json::{self, Object};
+use crate::span::Span;
+use std::error::Error;
+use std::fmt;
+
 /// What kind of invalid instruction was attempted to be parsed
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum InstType {
     Stack,
     Heap,
@@ -9,10 +15,32 @@ pub enum InstType {
     Unknown,
 }
 
+impl InstType {
+    /// Returns a stable identifier for the instruction type
+    pub fn name(&self) -> &'static str {
+        match self {
+            InstType::Stack => "stack",
+            InstType::Heap => "heap",
+            InstType::Io => "io",
+            InstType::ControlFlow => "control_flow",
+            InstType::Arithmetic => "arithmetic",
+            InstType::Unknown => "unknown",
+        }
+    }
+}
+
+/// The valid number literals as sequences of spaces (`S`), tabs (`T`) and
+/// newlines (`L`)
+const LITERALS: &[(&str, &str)] = &[
+    ("S<bits>L", "positive number, with bits · for 0 and → for 1"),
+    ("T<bits>L", "negative number, with bits · for 0 and → for 1"),
+];
+
 /// What kind of parse error was found
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ErrorKind {
-    /// The literal is too large to fit in an `i64`
+    /// The literal is too large to fit in an `i64`. With the `bignum`
+    /// feature this only applies to `Copy` and `Slide` operands
     LiteralOverflow,
     /// The literal could not be parsed
     InvalidLiteral,
@@ -24,56 +52,146 @@ pub enum ErrorKind {
     TooManyLabels,
     /// The source file ended before the program could be fully parsed
     UnexpectedEof,
+    /// A `Copy` or `Slide` instruction has a negative operand
+    NegativeOperand,
+    /// The label was already marked on the given line
+    DuplicateLabel(usize),
+    /// A jump or call targets a label marked after the last instruction,
+    /// outside any subroutine body
+    JumpPastEnd,
+}
+
+impl ErrorKind {
+    /// Returns a stable identifier for the kind of error
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::LiteralOverflow => "literal_overflow",
+            ErrorKind::InvalidLiteral => "invalid_literal",
+            ErrorKind::InvalidInstruction(_) => "invalid_instruction",
+            ErrorKind::InvalidLabel => "invalid_label",
+            ErrorKind::TooManyLabels => "too_many_labels",
+            ErrorKind::UnexpectedEof => "unexpected_eof",
+            ErrorKind::NegativeOperand => "negative_operand",
+            ErrorKind::DuplicateLabel(_) => "duplicate_label",
+            ErrorKind::JumpPastEnd => "jump_past_end",
+        }
+    }
+
+    /// Returns the token sequences that would have been valid where the
+    /// error was found, paired with what each one means
+    pub fn expected(&self) -> Vec<(String, &'static str)> {
+        match self {
+            ErrorKind::InvalidLiteral => LITERALS
+                .iter()
+                .map(|(sequence, meaning)| (sequence.to_string(), *meaning))
+                .collect(),
+            ErrorKind::InvalidInstruction(inst) => opcode::expected(*inst),
+            _ => vec![],
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::LiteralOverflow if cfg!(feature = "bignum") => {
+                write!(f, "Copy or slide operand too large.")
+            }
+            ErrorKind::LiteralOverflow => write!(f, "Literal too large to fit in an i64."),
+            ErrorKind::InvalidLiteral => write!(f, "Invalid literal."),
+            ErrorKind::InvalidInstruction(inst) => match inst {
+                InstType::Stack => write!(f, "Invalid stack manipulation instruction."),
+                InstType::Heap => write!(f, "Invalid heap manipulation instruction."),
+                InstType::Io => write!(f, "Invalid IO instruction."),
+                InstType::ControlFlow => write!(f, "Invalid control flow instruction."),
+                InstType::Arithmetic => write!(f, "Invalid arithmetic instruction."),
+                InstType::Unknown => write!(f, "Invalid instruction prefix."),
+            },
+            ErrorKind::InvalidLabel => write!(f, "Invalid Label."),
+            ErrorKind::TooManyLabels => write!(f, "Program contains too many labels."),
+            ErrorKind::UnexpectedEof => write!(f, "Unexpected end of file."),
+            ErrorKind::NegativeOperand => write!(f, "Negative copy or slide operand."),
+            ErrorKind::DuplicateLabel(line_no) => {
+                write!(f, "Label already marked on line {}.", line_no)
+            }
+            ErrorKind::JumpPastEnd => write!(f, "Jump target is past the end of the program."),
+        }
+    }
 }
 
+/// An error encountered while parsing a Whitespace program
 #[derive(Debug)]
 pub struct ParseError {
-    line_no: usize,
+    span: Span,
     kind: ErrorKind,
 }
 
 impl ParseError {
-    pub fn new(kind: ErrorKind, line_no: usize) -> Self {
-        Self { kind, line_no }
+    pub fn new(kind: ErrorKind, span: Span) -> Self {
+        Self { kind, span }
     }
 
-    /// Dumps the error to stdout
-    pub fn print_error(&self) {
-        print!("[Line {}] ", self.line_no);
+    /// Returns what kind of error was found
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the source line number the error was found on
+    pub fn line_no(&self) -> usize {
+        self.span.start.line
+    }
+
+    /// Returns the column the error was found at
+    pub fn column(&self) -> usize {
+        self.span.start.column
+    }
+
+    /// Returns the span of source the error was found in
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Renders the error along with an excerpt of `source`, the code the
+    /// error was found in, and the token sequences that would have been
+    /// valid
+    pub fn render(&self, source: &str) -> String {
+        render::diagnostic(self, source, self.span, &self.kind.expected())
+    }
+
+    /// Dumps the error rendered against `source` to stderr
+    pub fn print_error(&self, source: &str) {
+        eprintln!("{}", self.render(source));
+    }
+
+    /// Encodes the error as a single line JSON object
+    pub fn to_json(&self) -> String {
+        let mut object = Object::new()
+            .string("type", "parse_error")
+            .string("kind", self.kind.name());
         match self.kind {
-            ErrorKind::LiteralOverflow => {
-                println!("Literal too large to fit in an i64.");
-            }
-            ErrorKind::InvalidLiteral => {
-                println!("Invalid literal.");
-            }
-            ErrorKind::InvalidInstruction(inst) => match inst {
-                InstType::Stack => {
-                    println!("Invalid stack manipulation instruction.");
-                }
-                InstType::Heap => {
-                    println!("Invalid heap manipulation instruction.");
-                }
-                InstType::Io => {
-                    println!("Invalid IO instruction.");
-                }
-                InstType::ControlFlow => {
-                    println!("Invalid control flow instruction.");
-                }
-                InstType::Arithmetic => {
-                    println!("Invalid arithmetic instruction.");
-                }
-                InstType::Unknown => println!("Invalid instruction prefix."),
-            },
-            ErrorKind::InvalidLabel => {
-                println!("Invalid Label.");
-            }
-            ErrorKind::TooManyLabels => {
-                println!("Program contains too many labels.");
+            ErrorKind::InvalidInstruction(inst) => {
+                object = object.string("inst_type", inst.name());
             }
-            ErrorKind::UnexpectedEof => {
-                println!("Unexpected end of file.");
+            ErrorKind::DuplicateLabel(line_no) => {
+                object = object.number("previous_line", line_no);
             }
+            _ => {}
         }
+
+        object
+            .string("message", &self.kind.to_string())
+            .number("line", self.line_no())
+            .number("column", self.column())
+            .raw("span", &json::span(self.span))
+            .finish()
     }
 }
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let start = self.span.start;
+        write!(f, "[Line {}:{}] {}", start.line, start.column, self.kind)
+    }
+}
+
+impl Error for ParseError {}
@@ -1,12 +1,15 @@
-use std::collections::hash_map::Iter;
-use std::collections::HashMap;
+use crate::program::{Instruction, Program};
+use crate::span::Span;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 /// Represents a mapping between program labels, their
 /// associated program counters, and the program instructions
 /// containing jump targets
 pub struct LabelMap {
     pc_map: HashMap<usize, usize>,
-    inst_list: HashMap<usize, usize>,
+    /// Where in the source each label was marked
+    span_map: HashMap<usize, Span>,
+    inst_list: BTreeMap<usize, usize>,
 }
 
 impl LabelMap {
@@ -14,7 +17,8 @@ impl LabelMap {
     pub fn new() -> Self {
         Self {
             pc_map: HashMap::new(),
-            inst_list: HashMap::new(),
+            span_map: HashMap::new(),
+            inst_list: BTreeMap::new(),
         }
     }
 
@@ -25,13 +29,6 @@ impl LabelMap {
         self.pc_map.get(&label).cloned()
     }
 
-    /// Iterates over the instruction list, yielding a tuple `(idx, label)`
-    /// where `idx` is the index of the instruction in the current program
-    /// and `label` is the label corresponding to that instruction
-    pub fn iter_insts(&self) -> Iter<usize, usize> {
-        self.inst_list.iter()
-    }
-
     /// Adds a mapping from the index of a given jump instruction in the
     /// current program to its corresponding label that eventually needs
     /// to be resolved to a particular program counter
@@ -39,8 +36,79 @@ impl LabelMap {
         self.inst_list.insert(idx, label);
     }
 
-    /// Adds a mapping from a given label to its corresponding program counter
-    pub fn add_label(&mut self, label: usize, pc: usize) {
+    /// Adds a mapping from a given label to its corresponding program counter,
+    /// marked at the given span of source.
+    ///
+    /// If the label was already marked, the existing mapping is kept and
+    /// the line number it was marked on is returned as the error
+    pub fn add_label(&mut self, label: usize, pc: usize, span: Span) -> Result<(), usize> {
+        if let Some(prev_span) = self.span_map.get(&label) {
+            return Err(prev_span.start.line);
+        }
+
         self.pc_map.insert(label, pc);
+        self.span_map.insert(label, span);
+        Ok(())
+    }
+
+    /// Returns every label that is marked but never jumped to or called,
+    /// along with where it was marked, ordered by position in the source
+    pub fn unused_labels(&self) -> Vec<(usize, Span)> {
+        let used: HashSet<_> = self.inst_list.values().collect();
+        let mut unused: Vec<_> = self
+            .span_map
+            .iter()
+            .filter(|(label, _)| !used.contains(label))
+            .map(|(label, span)| (*label, *span))
+            .collect();
+        unused.sort_by_key(|(label, span)| (span.start.offset, *label));
+        unused
+    }
+
+    /// Walks through the given program and resolves all the jump targets,
+    /// recording every marked label in the program as well.
+    ///
+    /// On failure, returns the indices of the instructions that jump to a
+    /// label that was never marked. Those instructions are left unresolved
+    pub fn patch_jumps(&self, program: &mut Program) -> Result<(), Vec<usize>> {
+        let mut unresolved = vec![];
+
+        for (idx, label) in &self.inst_list {
+            let pc = match self.get_pc(*label) {
+                Some(x) => x,
+                None => {
+                    unresolved.push(*idx);
+                    continue;
+                }
+            };
+
+            let inst = program.inst_at_mut(*idx);
+            let mut add_label = false;
+            let new_inst = match inst {
+                Instruction::Call(_) => {
+                    add_label = true;
+                    Instruction::Call(pc)
+                }
+                Instruction::Jump(_) => Instruction::Jump(pc),
+                Instruction::JumpIfZero(_) => Instruction::JumpIfZero(pc),
+                Instruction::JumpIfNeg(_) => Instruction::JumpIfNeg(pc),
+                _ => unreachable!(),
+            };
+
+            *inst = new_inst;
+            if add_label {
+                program.add_sub_label(pc, *label);
+            }
+        }
+
+        for (label, pc) in &self.pc_map {
+            program.add_label(*label, *pc);
+        }
+
+        if unresolved.is_empty() {
+            Ok(())
+        } else {
+            Err(unresolved)
+        }
     }
 }
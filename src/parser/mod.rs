@@ -1,41 +1,74 @@
-use self::error::{ErrorKind, InstType, ParseError};
+pub use self::error::{ErrorKind, InstType, ParseError};
 use self::label_map::LabelMap;
+use self::opcode::{Op, Opcode};
+pub use self::output::ParseOutput;
+pub use self::warning::{Warning, WarningKind};
 use crate::program::{Instruction, Program};
+use crate::span::{Pos, Span};
 use crate::token::{Token, Tokens};
+use crate::value::Value;
 
+mod analysis;
 mod error;
-mod label_map;
+pub(crate) mod label_map;
+mod opcode;
+mod output;
+mod render;
+mod warning;
 
 /// A constant representing a dummy jump target that will be resolved later
 const UNINITIALIZED_JUMP_TARGET: usize = 0;
 
 type PResult<T> = Result<T, ParseError>;
 
+/// Compiles Whitespace source code into a `Program`
 pub struct Parser<'a> {
     tokens: Tokens<'a>,
-    /// The line number of the previous token
-    prev_line_no: usize,
+    /// The position just past the previous token
+    prev_end: Pos,
     curr: Option<Token>,
+    /// The position of the current token
+    curr_pos: Pos,
+    /// The position of the first token of the instruction being parsed
+    inst_start: Pos,
+    /// Whether the LF ending the instruction being parsed has been consumed,
+    /// in which case an error needs no recovery to reach the next one
+    inst_end: bool,
     labels: LabelMap,
     program: Program,
 }
 
 impl<'a> Parser<'a> {
+    /// Constructs a new parser over the given source code
     pub fn new(source: &'a str) -> Self {
         let tokens = Tokens::new(source);
 
         Self {
             tokens,
-            prev_line_no: 1,
+            prev_end: Pos::default(),
             curr: None,
+            curr_pos: Pos::default(),
+            inst_start: Pos::default(),
+            inst_end: false,
             labels: LabelMap::new(),
             program: Program::new(),
         }
     }
 
+    /// Returns the span of the instruction parsed so far
+    fn span(&self) -> Span {
+        Span::new(self.inst_start, self.prev_end)
+    }
+
     fn error(&self, kind: ErrorKind) -> ParseError {
-        let line_no = self.tokens.line_no();
-        ParseError::new(kind, line_no)
+        ParseError::new(kind, self.span())
+    }
+
+    /// Returns an error whose span also covers the current token, for when
+    /// that token is the one that could not be parsed
+    fn error_at_curr(&self, kind: ErrorKind) -> ParseError {
+        let span = Span::new(self.inst_start, self.tokens.end_pos());
+        ParseError::new(kind, span)
     }
 
     /// Returns an error indicating the parser has encountered an invalid
@@ -45,8 +78,9 @@ impl<'a> Parser<'a> {
     }
 
     fn get_next(&mut self) {
-        self.prev_line_no = self.tokens.line_no();
+        self.prev_end = self.tokens.end_pos();
         self.curr = self.tokens.next();
+        self.curr_pos = self.tokens.pos();
     }
 
     fn matches(&mut self, token: Token) -> bool {
@@ -74,46 +108,46 @@ impl<'a> Parser<'a> {
 
     /// Adds an instruction to the current program
     fn emit(&mut self, inst: Instruction) {
-        self.program.emit(inst, self.prev_line_no);
+        self.program.emit(inst, self.span());
     }
 
     /// Reads a number literal from the source
-    fn get_number(&mut self) -> PResult<i64> {
+    fn get_number(&mut self) -> PResult<Value> {
         let is_negative = if self.matches(Token::Space) {
             false
         } else if self.matches(Token::Tab) {
             true
         } else {
-            return Err(self.error(ErrorKind::InvalidLiteral));
+            return Err(self.error_at_curr(ErrorKind::InvalidLiteral));
         };
-        let mut num = 0i64;
+        let mut digits = vec![];
 
         loop {
             if self.matches(Token::Space) {
-                num = if let Some(x) = num.checked_shl(1) {
-                    x
-                } else {
-                    return Err(self.error(ErrorKind::LiteralOverflow));
-                };
+                digits.push(false);
             } else if self.matches(Token::Tab) {
-                num = if let Some(x) = num.checked_shl(1) {
-                    x
-                } else {
-                    return Err(self.error(ErrorKind::LiteralOverflow));
-                };
-                num |= 1;
+                digits.push(true);
             } else if self.matches(Token::Newline) {
+                self.inst_end = true;
                 break;
             } else {
                 return Err(self.error(ErrorKind::UnexpectedEof));
             }
         }
 
-        if is_negative {
-            num = -num;
+        match Value::from_binary(is_negative, &digits) {
+            Some(num) => Ok(num),
+            None => Err(self.error(ErrorKind::LiteralOverflow)),
         }
+    }
 
-        Ok(num)
+    /// Reads a number literal from the source that is used as an operand
+    /// to an instruction rather than pushed onto the stack
+    fn get_operand(&mut self) -> PResult<i64> {
+        match self.get_number()?.to_i64() {
+            Some(num) => Ok(num),
+            None => Err(self.error(ErrorKind::LiteralOverflow)),
+        }
     }
 
     /// Reads a label from the source
@@ -135,6 +169,7 @@ impl<'a> Parser<'a> {
                 };
                 label |= 1;
             } else if self.matches(Token::Newline) {
+                self.inst_end = true;
                 break;
             } else {
                 return Err(self.error(ErrorKind::UnexpectedEof));
@@ -144,229 +179,157 @@ impl<'a> Parser<'a> {
         Ok(label)
     }
 
-    fn get_stack_inst(&mut self) -> PResult<()> {
-        if self.matches(Token::Space) {
-            let num = self.get_number()?;
-            let idx = self.program.add_const(num);
-
-            let inst = Instruction::Push(idx);
-            self.emit(inst);
-
-            return Ok(());
-        }
-
-        let next_two = self.get_next_two()?;
-        match next_two {
-            (Token::Tab, Token::Space) => {
-                let num = self.get_number()?;
-                let inst = Instruction::Copy(num);
-                self.emit(inst);
-            }
-            (Token::Tab, Token::Newline) => {
-                let num = self.get_number()?;
-                let inst = Instruction::Slide(num);
-                self.emit(inst);
-            }
-            (Token::Newline, Token::Space) => {
-                let inst = Instruction::Dup;
-                self.emit(inst);
-            }
-            (Token::Newline, Token::Tab) => {
-                let inst = Instruction::Swap;
-                self.emit(inst);
+    /// Reads the tokens following the prefix of an instruction of the given
+    /// type, returning the instruction they decode to
+    fn decode(&mut self, inst_type: InstType) -> PResult<&'static Opcode> {
+        let opcodes = opcode::opcodes(inst_type);
+        for opcode in opcodes.iter().filter(|x| x.tokens.len() == 1) {
+            if self.matches(opcode.tokens[0]) {
+                return Ok(opcode);
             }
-            (Token::Newline, Token::Newline) => {
-                let inst = Instruction::Pop;
-                self.emit(inst);
-            }
-            _ => return Err(self.invalid_inst(InstType::Stack)),
         }
-
-        Ok(())
-    }
-
-    fn get_arith_inst(&mut self) -> PResult<()> {
-        let next_two = self.get_next_two()?;
-        match next_two {
-            (Token::Space, Token::Space) => {
-                let inst = Instruction::Add;
-                self.emit(inst);
-            }
-            (Token::Space, Token::Tab) => {
-                let inst = Instruction::Subtract;
-                self.emit(inst);
-            }
-            (Token::Space, Token::Newline) => {
-                let inst = Instruction::Multiply;
-                self.emit(inst);
-            }
-            (Token::Tab, Token::Space) => {
-                let inst = Instruction::Divide;
-                self.emit(inst);
-            }
-            (Token::Tab, Token::Tab) => {
-                let inst = Instruction::Modulo;
-                self.emit(inst);
-            }
-            _ => return Err(self.invalid_inst(InstType::Arithmetic)),
+        if opcodes.iter().all(|x| x.tokens.len() == 1) {
+            let kind = ErrorKind::InvalidInstruction(inst_type);
+            return Err(self.error_at_curr(kind));
         }
 
-        Ok(())
-    }
-
-    fn get_heap_inst(&mut self) -> PResult<()> {
-        if self.matches(Token::Space) {
-            let inst = Instruction::Store;
-            self.emit(inst);
-        } else if self.matches(Token::Tab) {
-            let inst = Instruction::Retrieve;
-            self.emit(inst);
-        } else {
-            return Err(self.invalid_inst(InstType::Heap));
+        let (first, second) = self.get_next_two()?;
+        match opcodes.iter().find(|x| x.tokens == [first, second]) {
+            Some(opcode) => Ok(opcode),
+            None => Err(self.invalid_inst(inst_type)),
         }
-
-        Ok(())
     }
 
-    fn get_io_inst(&mut self) -> PResult<()> {
-        let next_two = self.get_next_two()?;
-        match next_two {
-            (Token::Space, Token::Space) => {
-                let inst = Instruction::OutputChar;
-                self.emit(inst);
-            }
-            (Token::Space, Token::Tab) => {
-                let inst = Instruction::OutputNum;
-                self.emit(inst);
-            }
-            (Token::Tab, Token::Space) => {
-                let inst = Instruction::ReadChar;
-                self.emit(inst);
-            }
-            (Token::Tab, Token::Tab) => {
-                let inst = Instruction::ReadNum;
-                self.emit(inst);
+    /// Parses the rest of an instruction of the given type, once its prefix
+    /// has been read
+    fn get_typed_inst(&mut self, inst_type: InstType) -> PResult<()> {
+        let inst = match self.decode(inst_type)?.op {
+            Op::Push => {
+                let num = self.get_number()?;
+                Instruction::Push(self.program.add_const(num))
             }
-            _ => return Err(self.invalid_inst(InstType::Io)),
-        }
-
-        Ok(())
-    }
-
-    fn get_flow_inst(&mut self) -> PResult<()> {
-        let next_two = self.get_next_two()?;
-        match next_two {
-            (Token::Space, Token::Space) => {
+            Op::Operand(inst) => inst(self.get_operand()?),
+            Op::Mark => {
                 let label = self.get_label()?;
                 let pc = self.program.inst_count();
 
-                self.labels.add_label(label, pc);
-            }
-            (Token::Space, Token::Tab) => {
-                let label = self.get_label()?;
-                let idx = self.program.inst_count();
-                self.labels.add_inst(idx, label);
-
-                let inst = Instruction::Call(UNINITIALIZED_JUMP_TARGET);
-                self.emit(inst);
-            }
-            (Token::Space, Token::Newline) => {
-                let label = self.get_label()?;
-                let idx = self.program.inst_count();
-                self.labels.add_inst(idx, label);
-
-                let inst = Instruction::Jump(UNINITIALIZED_JUMP_TARGET);
-                self.emit(inst);
-            }
-            (Token::Tab, Token::Space) => {
-                let label = self.get_label()?;
-                let idx = self.program.inst_count();
-                self.labels.add_inst(idx, label);
-
-                let inst = Instruction::JumpIfZero(UNINITIALIZED_JUMP_TARGET);
-                self.emit(inst);
+                if let Err(prev_line_no) = self.labels.add_label(label, pc, self.span()) {
+                    return Err(self.error(ErrorKind::DuplicateLabel(prev_line_no)));
+                }
+                return Ok(());
             }
-            (Token::Tab, Token::Tab) => {
+            Op::Jump(inst) => {
                 let label = self.get_label()?;
                 let idx = self.program.inst_count();
                 self.labels.add_inst(idx, label);
 
-                let inst = Instruction::JumpIfNeg(UNINITIALIZED_JUMP_TARGET);
-                self.emit(inst);
+                inst(UNINITIALIZED_JUMP_TARGET)
             }
-            (Token::Tab, Token::Newline) => {
-                let inst = Instruction::Return;
-                self.emit(inst);
-            }
-            (Token::Newline, Token::Newline) => {
-                let inst = Instruction::End;
-                self.emit(inst);
-            }
-            _ => return Err(self.invalid_inst(InstType::ControlFlow)),
-        }
+            Op::Plain(inst) => inst,
+        };
 
+        self.emit(inst);
         Ok(())
     }
 
     /// Walk through the current program and resolve all the jump targets
     /// using the `LabelMap`
-    fn patch_jumps(&mut self) -> PResult<()> {
-        for (idx, label) in self.labels.iter_insts() {
-            let inst = self.program.inst_at_mut(*idx);
-            let pc = match self.labels.get_pc(*label) {
-                Some(x) => x,
-                None => {
-                    let line_no = self.program.line_at(*idx);
-                    return Err(ParseError::new(ErrorKind::InvalidLabel, line_no));
-                }
-            };
+    fn patch_jumps(&mut self, errors: &mut Vec<ParseError>) {
+        if let Err(idxs) = self.labels.patch_jumps(&mut self.program) {
+            for idx in idxs {
+                let span = self.program.span_at(idx);
+                errors.push(ParseError::new(ErrorKind::InvalidLabel, span));
+            }
+        }
+    }
 
-            let mut add_label = false;
-            let new_inst = match inst {
-                Instruction::Call(_) => {
-                    add_label = true;
-                    Instruction::Call(pc)
-                }
-                Instruction::Jump(_) => Instruction::Jump(pc),
-                Instruction::JumpIfZero(_) => Instruction::JumpIfZero(pc),
-                Instruction::JumpIfNeg(_) => Instruction::JumpIfNeg(pc),
-                _ => unreachable!(),
-            };
-
-            *inst = new_inst;
-            if add_label {
-                self.program.add_sub_label(pc, *label);
+    /// Parses a single instruction. Returns false if there are no more
+    /// instructions to parse
+    fn get_inst(&mut self) -> PResult<bool> {
+        self.inst_start = self.curr_pos;
+        self.inst_end = false;
+        let first = match self.curr {
+            Some(token) => token,
+            None => return Ok(false),
+        };
+        self.get_next();
+
+        // Types sharing the first token are told apart by the second
+        for (inst_type, prefix) in opcode::prefixes() {
+            if prefix[0] == first && prefix[1..].iter().all(|x| self.matches(*x)) {
+                self.get_typed_inst(inst_type)?;
+                return Ok(true);
             }
         }
+        Err(self.invalid_inst(InstType::Unknown))
+    }
 
-        Ok(())
+    /// Skips past the next LF after an error, so parsing can resume at what
+    /// is hopefully the start of another instruction
+    fn synchronize(&mut self) {
+        while let Some(token) = self.curr {
+            self.get_next();
+            if token == Token::Newline {
+                break;
+            }
+        }
     }
 
     /// Parses the input
-    pub fn parse(mut self) -> Result<Program, ParseError> {
+    pub fn parse(self) -> Result<Program, ParseError> {
+        self.parse_with_warnings().map(|(program, _)| program)
+    }
+
+    /// Parses the input, also returning warnings about constructs that are
+    /// valid but likely mistakes
+    pub fn parse_with_warnings(self) -> Result<(Program, Vec<Warning>), ParseError> {
+        let mut output = self.parse_all();
+        if output.errors.is_empty() {
+            Ok((output.program, output.warnings))
+        } else {
+            Err(output.errors.remove(0))
+        }
+    }
+
+    /// Parses the input, recovering from errors instead of stopping at the
+    /// first one. Returns every error and warning found along with whatever
+    /// could be parsed of the program.
+    ///
+    /// Warnings are only checked if there are no errors, since a program
+    /// with errors may be missing the instructions they depend on
+    pub fn parse_all(mut self) -> ParseOutput {
+        let mut errors = vec![];
         self.get_next();
 
         loop {
-            if self.matches(Token::Space) {
-                self.get_stack_inst()?;
-            } else if self.matches(Token::Tab) {
-                if self.matches(Token::Space) {
-                    self.get_arith_inst()?;
-                } else if self.matches(Token::Tab) {
-                    self.get_heap_inst()?;
-                } else if self.matches(Token::Newline) {
-                    self.get_io_inst()?;
-                } else {
-                    return Err(self.invalid_inst(InstType::Unknown));
+            match self.get_inst() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(error) => {
+                    errors.push(error);
+                    if !self.inst_end {
+                        self.synchronize();
+                    }
                 }
-            } else if self.matches(Token::Newline) {
-                self.get_flow_inst()?;
-            } else {
-                break;
             }
         }
-        self.patch_jumps()?;
+        self.patch_jumps(&mut errors);
+        analysis::check_operands(&self.program, &mut errors);
+        analysis::check_jumps(&self.program, &mut errors);
+
+        let mut warnings = vec![];
+        if errors.is_empty() {
+            analysis::check_fall_through(&self.program, &mut warnings);
+            analysis::check_returns(&self.program, &mut warnings);
+            for (label, span) in self.labels.unused_labels() {
+                warnings.push(Warning::new(WarningKind::UnusedLabel(label), span));
+            }
+        }
 
-        Ok(self.program)
+        ParseOutput {
+            program: self.program,
+            errors,
+            warnings,
+        }
     }
 }
@@ -0,0 +1,148 @@
+//! The token sequences of every instruction. The parser decodes with these
+//! tables, and errors list their entries as what would have been valid.
+
+use super::error::InstType;
+use crate::program::Instruction;
+use crate::token::Token;
+use Token::{Newline as L, Space as S, Tab as T};
+
+/// What a decoded instruction does with the operand that follows it
+#[derive(Copy, Clone)]
+pub(super) enum Op {
+    /// Pushes the number that follows
+    Push,
+    /// Takes the number that follows as its operand
+    Operand(fn(i64) -> Instruction),
+    /// Marks the label that follows
+    Mark,
+    /// Jumps to the label that follows
+    Jump(fn(usize) -> Instruction),
+    /// Takes no operand
+    Plain(Instruction),
+}
+
+impl Op {
+    /// Returns the placeholder for the operand, as shown in errors
+    fn operand(self) -> &'static str {
+        match self {
+            Op::Push | Op::Operand(_) => "<number>",
+            Op::Mark | Op::Jump(_) => "<label>",
+            Op::Plain(_) => "",
+        }
+    }
+}
+
+/// An instruction, as the tokens that follow its type's prefix
+pub(super) struct Opcode {
+    pub tokens: &'static [Token],
+    pub op: Op,
+    pub mnemonic: &'static str,
+}
+
+const fn opcode(tokens: &'static [Token], op: Op, mnemonic: &'static str) -> Opcode {
+    Opcode {
+        tokens,
+        op,
+        mnemonic,
+    }
+}
+
+/// The tokens each type of instruction starts with, and what the type is
+/// called when listing the types sharing the `T` prefix
+const PREFIXES: &[(InstType, &[Token], &str)] = &[
+    (InstType::Stack, &[S], "stack manipulation"),
+    (InstType::Arithmetic, &[T, S], "arithmetic"),
+    (InstType::Heap, &[T, T], "heap access"),
+    (InstType::Io, &[T, L], "I/O"),
+    (InstType::ControlFlow, &[L], "flow control"),
+];
+
+const STACK: &[Opcode] = &[
+    opcode(&[S], Op::Push, "push"),
+    opcode(&[T, S], Op::Operand(Instruction::Copy), "copy"),
+    opcode(&[T, L], Op::Operand(Instruction::Slide), "slide"),
+    opcode(&[L, S], Op::Plain(Instruction::Dup), "dup"),
+    opcode(&[L, T], Op::Plain(Instruction::Swap), "swap"),
+    opcode(&[L, L], Op::Plain(Instruction::Pop), "pop"),
+];
+const ARITHMETIC: &[Opcode] = &[
+    opcode(&[S, S], Op::Plain(Instruction::Add), "add"),
+    opcode(&[S, T], Op::Plain(Instruction::Subtract), "sub"),
+    opcode(&[S, L], Op::Plain(Instruction::Multiply), "mul"),
+    opcode(&[T, S], Op::Plain(Instruction::Divide), "div"),
+    opcode(&[T, T], Op::Plain(Instruction::Modulo), "mod"),
+];
+const HEAP: &[Opcode] = &[
+    opcode(&[S], Op::Plain(Instruction::Store), "store"),
+    opcode(&[T], Op::Plain(Instruction::Retrieve), "retrieve"),
+];
+const IO: &[Opcode] = &[
+    opcode(&[S, S], Op::Plain(Instruction::OutputChar), "outc"),
+    opcode(&[S, T], Op::Plain(Instruction::OutputNum), "outn"),
+    opcode(&[T, S], Op::Plain(Instruction::ReadChar), "readc"),
+    opcode(&[T, T], Op::Plain(Instruction::ReadNum), "readn"),
+];
+const CONTROL_FLOW: &[Opcode] = &[
+    opcode(&[S, S], Op::Mark, "label"),
+    opcode(&[S, T], Op::Jump(Instruction::Call), "call"),
+    opcode(&[S, L], Op::Jump(Instruction::Jump), "jmp"),
+    opcode(&[T, S], Op::Jump(Instruction::JumpIfZero), "jz"),
+    opcode(&[T, T], Op::Jump(Instruction::JumpIfNeg), "jn"),
+    opcode(&[T, L], Op::Plain(Instruction::Return), "ret"),
+    opcode(&[L, L], Op::Plain(Instruction::End), "end"),
+];
+
+/// Returns the instruction types, each with the tokens it starts with
+pub(super) fn prefixes() -> impl Iterator<Item = (InstType, &'static [Token])> {
+    PREFIXES
+        .iter()
+        .map(|(inst_type, prefix, _)| (*inst_type, *prefix))
+}
+
+/// Returns the instructions of the given type
+pub(super) fn opcodes(inst_type: InstType) -> &'static [Opcode] {
+    match inst_type {
+        InstType::Stack => STACK,
+        InstType::Arithmetic => ARITHMETIC,
+        InstType::Heap => HEAP,
+        InstType::Io => IO,
+        InstType::ControlFlow => CONTROL_FLOW,
+        InstType::Unknown => &[],
+    }
+}
+
+/// Writes tokens as `S`, `T` and `L`
+fn spell(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            S => 'S',
+            T => 'T',
+            L => 'L',
+        })
+        .collect()
+}
+
+/// Returns the token sequences valid after the prefix of the given type,
+/// written with `S`, `T` and `L` and paired with what each one means. For
+/// an unknown type these are the types sharing the `T` prefix
+pub(super) fn expected(inst_type: InstType) -> Vec<(String, &'static str)> {
+    if inst_type == InstType::Unknown {
+        return PREFIXES
+            .iter()
+            .filter(|(_, prefix, _)| prefix.len() > 1)
+            .map(|(_, prefix, name)| (spell(prefix), *name))
+            .collect();
+    }
+
+    let prefix = prefixes()
+        .find(|(x, _)| *x == inst_type)
+        .map_or(String::new(), |(_, prefix)| spell(prefix));
+    opcodes(inst_type)
+        .iter()
+        .map(|opcode| {
+            let sequence = format!("{}{}{}", prefix, spell(opcode.tokens), opcode.op.operand());
+            (sequence, opcode.mnemonic)
+        })
+        .collect()
+}
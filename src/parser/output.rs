@@ -0,0 +1,16 @@
+use super::error::ParseError;
+use super::warning::Warning;
+use crate::program::Program;
+
+/// Everything found while parsing a program with error recovery
+#[derive(Debug)]
+pub struct ParseOutput {
+    /// The parsed program. If there are any errors, this only contains the
+    /// instructions that could be parsed and its jump targets may be
+    /// unresolved
+    pub program: Program,
+    /// Every error found, in the order they were found
+    pub errors: Vec<ParseError>,
+    /// Every warning found
+    pub warnings: Vec<Warning>,
+}
@@ -0,0 +1,175 @@
+//! Renders diagnostics along with an excerpt of the source they refer to.
+//! Whitespace is invisible, so spaces, tabs and newlines are drawn as `·`,
+//! `→` and `↵`.
+
+use crate::span::{Pos, Span};
+use std::fmt::{Display, Write};
+
+/// The most characters of a single line shown in an excerpt
+const MAX_WIDTH: usize = 64;
+/// How many characters before the span to show on a line that is too long
+const LEAD: usize = 8;
+/// The most lines shown in an excerpt
+const MAX_LINES: usize = 4;
+
+const LEGEND: &str = "· is a space, → is a tab and ↵ is a newline";
+
+/// Returns the visible glyph used to draw `c`
+fn glyph(c: char) -> char {
+    match c {
+        ' ' => '·',
+        '\t' => '→',
+        '\n' => '↵',
+        c if c.is_control() => char::REPLACEMENT_CHARACTER,
+        c => c,
+    }
+}
+
+/// Draws a token sequence written with `S`, `T` and `L`, such as `TLSS`,
+/// using the glyphs of the excerpt. Placeholders in angle brackets are kept
+fn draw_sequence(sequence: &str) -> String {
+    let mut in_placeholder = false;
+    sequence
+        .chars()
+        .map(|c| match c {
+            '<' | '>' => {
+                in_placeholder = c == '<';
+                c
+            }
+            'S' if !in_placeholder => glyph(' '),
+            'T' if !in_placeholder => glyph('\t'),
+            'L' if !in_placeholder => glyph('\n'),
+            c => c,
+        })
+        .collect()
+}
+
+/// Returns true if the character at `line` and `column` lies in the span
+fn in_span(span: Span, line: usize, column: usize) -> bool {
+    let pos = (line, column);
+    let start = (span.start.line, span.start.column);
+    let end = (span.end.line, span.end.column);
+
+    if start >= end {
+        pos == start
+    } else {
+        start <= pos && pos < end
+    }
+}
+
+/// Returns the last line the span covers any characters of
+fn last_line(span: Span) -> usize {
+    let Pos { line, column, .. } = span.end;
+    if line > span.start.line && column == 1 {
+        line - 1
+    } else {
+        line
+    }
+}
+
+/// Returns the last line of the span that is shown in an excerpt
+fn last_shown(span: Span) -> usize {
+    last_line(span).min(span.start.line + MAX_LINES - 1)
+}
+
+/// Draws a single source line and the underline beneath it
+fn draw_line(text: &str, line_no: usize, span: Span) -> (String, String) {
+    let chars: Vec<_> = text.chars().collect();
+    let marked: Vec<_> = (0..chars.len())
+        .map(|idx| in_span(span, line_no, idx + 1))
+        .collect();
+
+    // Keep the start of the span in view on long lines
+    let mut lo = 0;
+    if chars.len() > MAX_WIDTH {
+        let first = marked.iter().position(|x| *x).unwrap_or(chars.len());
+        lo = first.saturating_sub(LEAD).min(chars.len() - MAX_WIDTH);
+    }
+    let hi = (lo + MAX_WIDTH).min(chars.len());
+
+    let mut line = String::new();
+    let mut underline = String::new();
+    if lo > 0 {
+        line.push('…');
+        underline.push(' ');
+    }
+    for idx in lo..hi {
+        line.push(glyph(chars[idx]));
+        underline.push(if marked[idx] { '^' } else { ' ' });
+    }
+    if hi < chars.len() {
+        line.push('…');
+    }
+
+    // A span starting at the very end of the source has nothing to mark
+    if !marked.contains(&true) && line_no == span.start.line {
+        underline.push('^');
+    }
+
+    (line, underline.trim_end().to_string())
+}
+
+/// Draws the lines of `source` covered by `span`, with the span underlined
+pub(crate) fn excerpt(source: &str, span: Span) -> String {
+    let first = span.start.line;
+    let last = last_shown(span);
+    let width = last.to_string().len();
+    let gutter = " ".repeat(width);
+
+    let mut out = String::new();
+    writeln!(out, "{} |", gutter).unwrap();
+
+    let mut lines = source.split_inclusive('\n').skip(first - 1);
+    for line_no in first..=last {
+        let text = lines.next().unwrap_or("");
+        let (line, underline) = draw_line(text, line_no, span);
+        let line = format!("{:>width$} | {}", line_no, line, width = width);
+        writeln!(out, "{}", line.trim_end()).unwrap();
+        if !underline.is_empty() {
+            writeln!(out, "{} | {}", gutter, underline).unwrap();
+        }
+    }
+
+    if last < last_line(span) {
+        writeln!(out, "{} | …", gutter).unwrap();
+    }
+
+    out
+}
+
+/// Renders a diagnostic message followed by an excerpt of the source at
+/// `span` and, if given, the token sequences that would have been valid
+pub(crate) fn diagnostic<D: Display>(
+    message: D,
+    source: &str,
+    span: Span,
+    expected: &[(String, &str)],
+) -> String {
+    let mut out = format!("{}\n{}", message, excerpt(source, span));
+    let gutter = " ".repeat(last_shown(span).to_string().len());
+
+    if !expected.is_empty() {
+        writeln!(out, "{} = expected one of:", gutter).unwrap();
+        let sequences: Vec<_> = expected.iter().map(|(x, _)| draw_sequence(x)).collect();
+        let align = sequences
+            .iter()
+            .map(|x| x.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (sequence, (_, meaning)) in sequences.iter().zip(expected) {
+            let pad = align - sequence.chars().count();
+            writeln!(
+                out,
+                "{}       {}{}  {}",
+                gutter,
+                sequence,
+                " ".repeat(pad),
+                meaning
+            )
+            .unwrap();
+        }
+    }
+
+    write!(out, "{} = note: {}", gutter, LEGEND).unwrap();
+    out
+}
@@ -0,0 +1,104 @@
+use super::render;
+use crate::json::{self, Object};
+use crate::span::Span;
+use std::fmt;
+
+/// What kind of suspicious construct was found
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WarningKind {
+    /// Execution can run past the last instruction without reaching an
+    /// `End`
+    FallsOffEnd,
+    /// A `Return` can be reached from the main program, where there is no
+    /// caller to return to
+    ReturnFromMain,
+    /// The label is marked but never jumped to or called
+    UnusedLabel(usize),
+}
+
+impl WarningKind {
+    /// Returns a stable identifier for the kind of warning
+    pub fn name(&self) -> &'static str {
+        match self {
+            WarningKind::FallsOffEnd => "falls_off_end",
+            WarningKind::ReturnFromMain => "return_from_main",
+            WarningKind::UnusedLabel(_) => "unused_label",
+        }
+    }
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WarningKind::FallsOffEnd => {
+                write!(f, "Execution can run off the end of the program.")
+            }
+            WarningKind::ReturnFromMain => {
+                write!(f, "Return reachable from main with no caller.")
+            }
+            WarningKind::UnusedLabel(label) => write!(f, "Label #{} is never used.", label),
+        }
+    }
+}
+
+/// A construct that is valid but likely a mistake
+#[derive(Debug)]
+pub struct Warning {
+    span: Span,
+    kind: WarningKind,
+}
+
+impl Warning {
+    pub fn new(kind: WarningKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Returns what kind of warning was found
+    pub fn kind(&self) -> WarningKind {
+        self.kind
+    }
+
+    /// Returns the source line number the warning was found on
+    pub fn line_no(&self) -> usize {
+        self.span.start.line
+    }
+
+    /// Returns the span of source the warning was found in
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Renders the warning along with an excerpt of `source`, the code the
+    /// warning was found in
+    pub fn render(&self, source: &str) -> String {
+        render::diagnostic(self, source, self.span, &[])
+    }
+
+    /// Encodes the warning as a single line JSON object
+    pub fn to_json(&self) -> String {
+        let mut object = Object::new()
+            .string("type", "warning")
+            .string("kind", self.kind.name());
+        if let WarningKind::UnusedLabel(label) = self.kind {
+            object = object.number("label", label);
+        }
+
+        object
+            .string("message", &self.kind.to_string())
+            .number("line", self.line_no())
+            .number("column", self.span.start.column)
+            .raw("span", &json::span(self.span))
+            .finish()
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let start = self.span.start;
+        write!(
+            f,
+            "[Line {}:{}] Warning: {}",
+            start.line, start.column, self.kind
+        )
+    }
+}
@@ -1,6 +1,12 @@
+use crate::span::Span;
+use crate::value::Value;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+/// A single bytecode instruction
+///
+/// Jump targets are stored as resolved program counters and `Push` refers to
+/// an entry in the program's constant pool.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Instruction {
     Push(usize),
     Dup,
@@ -27,29 +33,39 @@ pub enum Instruction {
     ReadNum,
 }
 
+/// A compiled Whitespace program
 #[derive(Debug)]
 pub struct Program {
     instructions: Vec<Instruction>,
-    line_nos: Vec<usize>,
-    constants: Vec<i64>,
+    /// The span of source each instruction was compiled from
+    spans: Vec<Span>,
+    constants: Vec<Value>,
     /// A mapping between subroutine labels and their corresponding
     /// program counters
     sub_labels: HashMap<usize, usize>,
+    /// A mapping between every label marked in the program and the
+    /// program counter it marks
+    labels: HashMap<usize, usize>,
+    /// Symbolic names for labels, if the program was assembled from
+    /// mnemonics
+    label_names: HashMap<usize, String>,
 }
 
 impl Program {
-    pub fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             instructions: vec![],
-            line_nos: vec![],
+            spans: vec![],
             constants: vec![],
             sub_labels: HashMap::new(),
+            labels: HashMap::new(),
+            label_names: HashMap::new(),
         }
     }
 
     /// Adds a new constant to the constant pool if it is not in the pool
     /// already and returns the index of the constant in the pool
-    pub fn add_const(&mut self, constant: i64) -> usize {
+    pub(crate) fn add_const(&mut self, constant: Value) -> usize {
         let idx = self.constants.iter().position(|x| *x == constant);
         match idx {
             Some(idx) => idx,
@@ -61,8 +77,8 @@ impl Program {
     }
 
     /// Fetches the constant at the given index
-    pub fn get_const(&self, idx: usize) -> i64 {
-        self.constants[idx]
+    pub fn get_const(&self, idx: usize) -> &Value {
+        &self.constants[idx]
     }
 
     /// Fetches the subroutine label for the given program counter if
@@ -72,23 +88,88 @@ impl Program {
     }
 
     /// Adds a subroutine label
-    pub fn add_sub_label(&mut self, pc: usize, label: usize) {
+    pub(crate) fn add_sub_label(&mut self, pc: usize, label: usize) {
         self.sub_labels.insert(pc, label);
     }
 
+    /// Records that `label` marks the given program counter
+    pub(crate) fn add_label(&mut self, label: usize, pc: usize) {
+        self.labels.insert(label, pc);
+    }
+
+    /// Fetches the program counter marked by `label` if it exists
+    pub fn label_pc(&self, label: usize) -> Option<usize> {
+        self.labels.get(&label).cloned()
+    }
+
+    /// Returns all the labels marking the given program counter, in
+    /// ascending order
+    pub fn labels_at(&self, pc: usize) -> Vec<usize> {
+        let mut labels: Vec<_> = self
+            .labels
+            .iter()
+            .filter(|(_, x)| **x == pc)
+            .map(|(label, _)| *label)
+            .collect();
+        labels.sort_unstable();
+        labels
+    }
+
+    /// Returns a label that marks nothing in the program, to name jumps
+    /// whose target could not be resolved
+    pub(crate) fn unmarked_label(&self) -> usize {
+        (0..).find(|x| !self.labels.contains_key(x)).unwrap()
+    }
+
+    /// Gives a symbolic name to a label
+    pub(crate) fn set_label_name(&mut self, label: usize, name: String) {
+        self.label_names.insert(label, name);
+    }
+
+    /// Fetches the symbolic name of a label if it has one
+    pub fn label_name(&self, label: usize) -> Option<&str> {
+        self.label_names.get(&label).map(String::as_str)
+    }
+
+    /// Looks up a label by the name it is displayed with, which is either
+    /// its symbolic name, its number, or its number prefixed with `label_`
+    pub fn find_label(&self, name: &str) -> Option<usize> {
+        let by_name = self
+            .label_names
+            .iter()
+            .find(|(_, x)| x.as_str() == name)
+            .map(|(label, _)| *label);
+        if by_name.is_some() {
+            return by_name;
+        }
+
+        let number = name.strip_prefix("label_").unwrap_or(name);
+        number.parse().ok().filter(|x| self.labels.contains_key(x))
+    }
+
     /// Returns a reference to the instruction at `idx`
     pub fn inst_at(&self, idx: usize) -> &Instruction {
         &self.instructions[idx]
     }
 
     /// Returns a mutable reference to the instruction at `idx`
-    pub fn inst_at_mut(&mut self, idx: usize) -> &mut Instruction {
+    pub(crate) fn inst_at_mut(&mut self, idx: usize) -> &mut Instruction {
         &mut self.instructions[idx]
     }
 
+    /// Returns all the instructions in the program
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
     /// Returns the source line number of the instruction at `idx`
     pub fn line_at(&self, idx: usize) -> usize {
-        self.line_nos[idx]
+        self.spans[idx].start.line
+    }
+
+    /// Returns the span of source the instruction at `idx` was compiled from
+    pub fn span_at(&self, idx: usize) -> Span {
+        self.spans[idx]
     }
 
     /// Gets the number of instructions currently added to the program
@@ -96,10 +177,10 @@ impl Program {
         self.instructions.len()
     }
 
-    /// Adds a new instruction to the program with its corresponding line
-    /// number in the source
-    pub fn emit(&mut self, inst: Instruction, line_no: usize) {
-        self.line_nos.push(line_no);
+    /// Adds a new instruction to the program with the span of source it was
+    /// compiled from
+    pub(crate) fn emit(&mut self, inst: Instruction, span: Span) {
+        self.spans.push(span);
         self.instructions.push(inst);
     }
 }
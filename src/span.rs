@@ -0,0 +1,34 @@
+/// A position in the source code
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Pos {
+    /// The byte offset from the start of the source
+    pub offset: usize,
+    /// The line number, starting from 1
+    pub line: usize,
+    /// The column in characters, starting from 1
+    pub column: usize,
+}
+
+impl Default for Pos {
+    /// The position of the start of the source
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+/// A range of source code, from `start` up to but not including `end`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: Pos,
+    pub end: Pos,
+}
+
+impl Span {
+    pub fn new(start: Pos, end: Pos) -> Self {
+        Self { start, end }
+    }
+}
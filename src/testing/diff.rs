@@ -0,0 +1,215 @@
+use std::fmt::Write;
+
+/// How many unchanged lines are shown around each change
+const CONTEXT: usize = 3;
+
+/// A single line of an edit script turning `expected` into `actual`
+#[derive(Copy, Clone)]
+enum Edit {
+    /// The line at the given index of `expected` is also in `actual`
+    Same(usize),
+    /// The line at the given index of `expected` is missing from `actual`
+    Removed(usize),
+    /// The line at the given index of `actual` is not in `expected`
+    Added(usize),
+}
+
+/// Finds the middle snake of the shortest edit script turning `old` into
+/// `new`: a run of matching lines that an optimal script passes through
+/// about halfway along. Returns the positions in both lists where it starts
+/// and ends, using Myers' algorithm from both ends at once so only linear
+/// space is needed
+fn middle_snake(old: &[&str], new: &[&str]) -> ((usize, usize), (usize, usize)) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2 + 1;
+
+    // forward[k] is the furthest position in `old` reached on diagonal k
+    // going forwards, and backward[k] the furthest from the end going
+    // backwards, where diagonal k holds the positions with x - y = k
+    let idx = |k: isize| (k + max) as usize;
+    let mut forward = vec![0; 2 * max as usize + 1];
+    let mut backward = vec![0; 2 * max as usize + 1];
+
+    for d in 0..max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[idx(k - 1)] < forward[idx(k + 1)]) {
+                forward[idx(k + 1)]
+            } else {
+                forward[idx(k - 1)] + 1
+            };
+            let start = (x, x - k);
+            while x < n && x - k < m && old[x as usize] == new[(x - k) as usize] {
+                x += 1;
+            }
+            forward[idx(k)] = x;
+
+            let c = delta - k;
+            if odd && (-(d - 1)..=d - 1).contains(&c) && x + backward[idx(c)] >= n {
+                let start = (start.0 as usize, start.1 as usize);
+                return (start, (x as usize, (x - k) as usize));
+            }
+        }
+
+        for c in (-d..=d).step_by(2) {
+            let mut x = if c == -d || (c != d && backward[idx(c - 1)] < backward[idx(c + 1)]) {
+                backward[idx(c + 1)]
+            } else {
+                backward[idx(c - 1)] + 1
+            };
+            let end = (n - x, m - (x - c));
+            while x < n && x - c < m && old[(n - x - 1) as usize] == new[(m - (x - c) - 1) as usize]
+            {
+                x += 1;
+            }
+            backward[idx(c)] = x;
+
+            let k = delta - c;
+            if !odd && (-d..=d).contains(&k) && x + forward[idx(k)] >= n {
+                let start = ((n - x) as usize, (m - (x - c)) as usize);
+                return (start, (end.0 as usize, end.1 as usize));
+            }
+        }
+    }
+    unreachable!("the two searches always meet")
+}
+
+/// Appends the shortest edit script turning `old` into `new` to `script`,
+/// where the lists start at the given lines of the full lists
+fn diff_region(old: &[&str], new: &[&str], (x, y): (usize, usize), script: &mut Vec<Edit>) {
+    // Lines shared at either end are matched up front, which also
+    // guarantees the middle snake splits what is left into smaller regions
+    let prefix = old.iter().zip(new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+    script.extend((x..x + prefix).map(Edit::Same));
+
+    let end = old.len() - suffix;
+    let (old, new) = (&old[prefix..end], &new[prefix..new.len() - suffix]);
+    let (x, y) = (x + prefix, y + prefix);
+    if old.is_empty() {
+        script.extend((y..y + new.len()).map(Edit::Added));
+    } else if new.is_empty() {
+        script.extend((x..x + old.len()).map(Edit::Removed));
+    } else {
+        let ((x0, y0), (x1, y1)) = middle_snake(old, new);
+        diff_region(&old[..x0], &new[..y0], (x, y), script);
+        script.extend((x + x0..x + x1).map(Edit::Same));
+        diff_region(&old[x1..], &new[y1..], (x + x1, y + y1), script);
+    }
+    script.extend((x - prefix + end..x - prefix + end + suffix).map(Edit::Same));
+}
+
+/// Computes the shortest edit script between two lists of lines
+fn edits(expected: &[&str], actual: &[&str]) -> Vec<Edit> {
+    let mut script = vec![];
+    diff_region(expected, actual, (0, 0), &mut script);
+
+    // Within each run of changes, show the removed lines before the added
+    // ones the way `diff -u` does
+    for run in script.split_mut(|x| matches!(x, Edit::Same(_))) {
+        run.sort_by_key(|x| matches!(x, Edit::Added(_)));
+    }
+    script
+}
+
+/// Writes a line of a hunk, marking lines that do not end in a newline
+fn write_line(out: &mut String, marker: char, line: &str) {
+    match line.strip_suffix('\n') {
+        Some(line) => writeln!(out, "{}{}", marker, line).unwrap(),
+        None => {
+            writeln!(out, "{}{}", marker, line).unwrap();
+            out.push_str("\\ No newline at end of file\n");
+        }
+    }
+}
+
+/// Formats the range of a hunk the way `diff -u` does, where an empty
+/// range starts at the line before it
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Returns a unified diff turning `expected` into `actual`, or an empty
+/// string if they are the same
+pub fn unified_diff(expected: &str, actual: &str) -> String {
+    let old: Vec<_> = expected.split_inclusive('\n').collect();
+    let new: Vec<_> = actual.split_inclusive('\n').collect();
+    let script = edits(&old, &new);
+
+    let changes: Vec<_> = script
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Same(_)))
+        .map(|(idx, _)| idx)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // Changes close enough that their context would touch share a hunk
+    let mut hunks = vec![];
+    let mut start = changes[0];
+    let mut end = changes[0];
+    for &idx in &changes[1..] {
+        if idx - end - 1 > 2 * CONTEXT {
+            hunks.push((start, end));
+            start = idx;
+        }
+        end = idx;
+    }
+    hunks.push((start, end));
+
+    let mut out = String::from("--- expected\n+++ actual\n");
+    for (start, end) in hunks {
+        let start = start.saturating_sub(CONTEXT);
+        let end = (end + CONTEXT + 1).min(script.len());
+        let edits = &script[start..end];
+
+        // The position in each file where the hunk starts
+        let (mut old_start, mut new_start) = (0, 0);
+        for edit in &script[..start] {
+            match edit {
+                Edit::Same(_) => {
+                    old_start += 1;
+                    new_start += 1;
+                }
+                Edit::Removed(_) => old_start += 1,
+                Edit::Added(_) => new_start += 1,
+            }
+        }
+        let old_len = edits
+            .iter()
+            .filter(|x| !matches!(x, Edit::Added(_)))
+            .count();
+        let new_len = edits
+            .iter()
+            .filter(|x| !matches!(x, Edit::Removed(_)))
+            .count();
+
+        writeln!(
+            out,
+            "@@ -{} +{} @@",
+            range(old_start, old_len),
+            range(new_start, new_len)
+        )
+        .unwrap();
+        for edit in edits {
+            match *edit {
+                Edit::Same(x) => write_line(&mut out, ' ', old[x]),
+                Edit::Removed(x) => write_line(&mut out, '-', old[x]),
+                Edit::Added(x) => write_line(&mut out, '+', new[x]),
+            }
+        }
+    }
+    out
+}
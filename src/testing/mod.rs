@@ -0,0 +1,309 @@
+//! A test runner for Whitespace programs with input and output fixtures.
+//!
+//! Every `NAME.ws` under a directory is a test case if it has an expected
+//! output in `NAME.out`, an expected runtime error in `NAME.err`, or both.
+//! The program reads `NAME.in` as its input if it exists, and no input
+//! otherwise.
+//!
+//! A case passes if the program prints exactly the contents of `NAME.out`
+//! and halts the way `NAME.err` describes. Without a `NAME.err` the program
+//! must run to completion. Otherwise the file holds the name of the
+//! [`RuntimeError`](crate::RuntimeError) the program must halt with, such
+//! as `zero_division`, or is empty if any runtime error will do.
+//!
+//! Cases are run in parallel, each with an instruction budget so a program
+//! stuck in a loop fails instead of hanging the run.
+
+pub use self::diff::unified_diff;
+use crate::parser::Parser;
+use crate::vm::{Config, Vm};
+use std::cell::Cell;
+use std::fmt;
+use std::fs;
+use std::io::{self, Cursor};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Once;
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod diff;
+pub mod report;
+
+/// The instruction budget of each case unless another one is given
+pub const DEFAULT_FUEL: u64 = 100_000_000;
+
+thread_local! {
+    /// Whether the thread is running a case, whose panics are reported as
+    /// failures instead of by the panic hook
+    static IN_CASE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Keeps the panic hook quiet while a case is running, so a panic does not
+/// print in the middle of a report. Other panics are printed as usual
+fn silence_panics() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !IN_CASE.with(Cell::get) {
+                hook(info);
+            }
+        }));
+    });
+}
+
+/// A program along with the fixtures it is checked against
+#[derive(Clone, Debug)]
+pub struct TestCase {
+    /// The path of the program relative to where it was discovered from,
+    /// without the `.ws` extension
+    pub name: String,
+    pub source: PathBuf,
+    /// The file the program reads its input from, if any
+    pub input: Option<PathBuf>,
+    /// The file holding the output the program must print, if any
+    pub output: Option<PathBuf>,
+    /// The file naming the runtime error the program must halt with, if any
+    pub error: Option<PathBuf>,
+}
+
+impl TestCase {
+    /// Constructs a case for the program at `source`, picking up whichever
+    /// fixtures exist next to it
+    pub fn new(name: String, source: PathBuf) -> Self {
+        let fixture = |extension| Some(source.with_extension(extension)).filter(|x| x.is_file());
+        Self {
+            name,
+            input: fixture("in"),
+            output: fixture("out"),
+            error: fixture("err"),
+            source,
+        }
+    }
+}
+
+/// Why a test case failed
+#[derive(Debug)]
+pub enum Failure {
+    /// A file belonging to the case could not be read
+    Io(PathBuf),
+    /// The program could not be parsed. Holds every error rendered against
+    /// the source
+    Parse(Vec<String>),
+    /// The program halted with an error it was not expected to
+    UnexpectedError(String),
+    /// The program ran to completion instead of halting with an error
+    MissingError(String),
+    /// The program halted with a different error than expected
+    WrongError { expected: String, actual: String },
+    /// The program printed something other than the expected output. Holds
+    /// a unified diff from the expected to the actual output
+    Output(String),
+    /// The interpreter panicked while running the case. Holds the panic
+    /// message
+    Panic(String),
+}
+
+impl Failure {
+    /// Returns the full explanation of the failure, such as a traceback or
+    /// a diff, or an empty string if the summary says it all
+    pub fn details(&self) -> String {
+        match self {
+            Failure::Parse(errors) => errors.join("\n"),
+            Failure::UnexpectedError(traceback) => traceback.clone(),
+            Failure::Output(diff) => diff.clone(),
+            Failure::Panic(message) => message.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Io(path) => write!(f, "could not read '{}'", path.display()),
+            Failure::Parse(errors) => write!(f, "{} parse error(s)", errors.len()),
+            Failure::UnexpectedError(_) => write!(f, "the program halted with an error"),
+            Failure::MissingError(expected) if expected.is_empty() => {
+                write!(f, "expected a runtime error, but the program succeeded")
+            }
+            Failure::MissingError(expected) => {
+                write!(f, "expected {}, but the program succeeded", expected)
+            }
+            Failure::WrongError { expected, actual } => {
+                write!(
+                    f,
+                    "expected {}, but the program halted with {}",
+                    expected, actual
+                )
+            }
+            Failure::Output(_) => write!(f, "the output differs from what was expected"),
+            Failure::Panic(_) => write!(f, "the interpreter panicked"),
+        }
+    }
+}
+
+/// The result of running a single test case
+#[derive(Debug)]
+pub struct TestResult {
+    pub name: String,
+    /// Why the case failed, or `None` if it passed
+    pub failure: Option<Failure>,
+    pub duration: Duration,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Finds every test case under `path`, sorted by name. If `path` is a file
+/// it is the only case, whether or not it has any fixtures
+pub fn discover<P: AsRef<Path>>(path: P) -> io::Result<Vec<TestCase>> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        let name = path.with_extension("").display().to_string();
+        fs::metadata(path)?;
+        return Ok(vec![TestCase::new(name, path.to_path_buf())]);
+    }
+
+    let mut cases = vec![];
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?.path();
+            if entry.is_dir() {
+                dirs.push(entry);
+            } else if entry.extension().is_some_and(|x| x == "ws") {
+                let relative = entry.strip_prefix(path).unwrap_or(&entry);
+                let name = relative.with_extension("").display().to_string();
+                let case = TestCase::new(name, entry);
+                if case.output.is_some() || case.error.is_some() {
+                    cases.push(case);
+                }
+            }
+        }
+    }
+
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(cases)
+}
+
+/// Runs the case, returning why it failed if it did
+fn check(case: &TestCase, config: &Config) -> Result<(), Failure> {
+    let read = |path: &Path| fs::read(path).map_err(|_| Failure::Io(path.to_path_buf()));
+    let read_string =
+        |path: &Path| fs::read_to_string(path).map_err(|_| Failure::Io(path.to_path_buf()));
+
+    let source = read_string(&case.source)?;
+    let input = match &case.input {
+        Some(path) => read(path)?,
+        None => vec![],
+    };
+    let expected_error = match &case.error {
+        Some(path) => Some(read_string(path)?.trim().to_string()),
+        None => None,
+    };
+
+    let parsed = Parser::new(&source).parse_all();
+    if !parsed.errors.is_empty() {
+        let errors = parsed.errors.iter().map(|x| x.render(&source)).collect();
+        return Err(Failure::Parse(errors));
+    }
+
+    let mut output = vec![];
+    let result = Vm::with_io(&parsed.program, Cursor::new(input), &mut output)
+        .with_config(config.clone())
+        .run();
+
+    match (result, &expected_error) {
+        (Ok(_), Some(expected)) => return Err(Failure::MissingError(expected.clone())),
+        (Err(traceback), None) => return Err(Failure::UnexpectedError(traceback.to_string())),
+        (Err(traceback), Some(expected)) => {
+            let actual = traceback.reason.name();
+            if !expected.is_empty() && expected != actual {
+                return Err(Failure::WrongError {
+                    expected: expected.clone(),
+                    actual: actual.to_string(),
+                });
+            }
+        }
+        (Ok(_), None) => {}
+    }
+
+    if let Some(path) = &case.output {
+        let expected = read(path)?;
+        if output != expected {
+            let expected = String::from_utf8_lossy(&expected);
+            let actual = String::from_utf8_lossy(&output);
+            return Err(Failure::Output(unified_diff(&expected, &actual)));
+        }
+    }
+    Ok(())
+}
+
+/// Runs a single test case with the given VM options. As a last resort, a
+/// panic in the interpreter fails the case rather than the whole run
+pub fn run_case(case: &TestCase, config: &Config) -> TestResult {
+    silence_panics();
+    let start = Instant::now();
+    IN_CASE.with(|x| x.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| check(case, config)));
+    IN_CASE.with(|x| x.set(false));
+    let failure = match result {
+        Ok(result) => result.err(),
+        Err(payload) => {
+            let message = match payload.downcast::<String>() {
+                Ok(message) => *message,
+                Err(payload) => payload
+                    .downcast_ref::<&str>()
+                    .map_or("unknown panic", |x| *x)
+                    .to_string(),
+            };
+            Some(Failure::Panic(message))
+        }
+    };
+    TestResult {
+        name: case.name.clone(),
+        failure,
+        duration: start.elapsed(),
+    }
+}
+
+/// Runs every case on `jobs` threads, returning the results in the same
+/// order as the cases
+pub fn run_all(cases: &[TestCase], config: &Config, jobs: usize) -> Vec<TestResult> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.clamp(1, cases.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        match cases.get(idx) {
+                            Some(case) => results.push((idx, run_case(case, config))),
+                            None => return results,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|x| x.join().unwrap())
+            .collect()
+    });
+
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Returns the number of threads to run cases on by default
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |x| x.get())
+}
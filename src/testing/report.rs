@@ -0,0 +1,140 @@
+//! Formats the results of a test run for people and for CI systems
+
+use super::TestResult;
+use std::fmt::Write;
+
+/// The name the whole run is reported under in JUnit XML
+const SUITE_NAME: &str = "wspace";
+
+/// Counts how many of the results passed and failed
+fn counts(results: &[TestResult]) -> (usize, usize) {
+    let passed = results.iter().filter(|x| x.passed()).count();
+    (passed, results.len() - passed)
+}
+
+/// Formats the results as a line per case followed by the details of every
+/// failure and a summary
+pub fn human(results: &[TestResult]) -> String {
+    let mut out = String::new();
+    for result in results {
+        let status = if result.passed() { "ok" } else { "FAILED" };
+        writeln!(out, "test {} ... {}", result.name, status).unwrap();
+    }
+
+    for result in results {
+        if let Some(failure) = &result.failure {
+            writeln!(out, "\n---- {} ----", result.name).unwrap();
+            writeln!(out, "{}", failure).unwrap();
+            let details = failure.details();
+            if !details.is_empty() {
+                writeln!(out, "{}", details.trim_end()).unwrap();
+            }
+        }
+    }
+
+    let (passed, failed) = counts(results);
+    let status = if failed == 0 { "ok" } else { "FAILED" };
+    write!(
+        out,
+        "\ntest result: {}. {} passed; {} failed",
+        status, passed, failed
+    )
+    .unwrap();
+    out
+}
+
+/// Formats the results in the Test Anything Protocol, with the details of
+/// each failure as comment lines
+pub fn tap(results: &[TestResult]) -> String {
+    let mut out = format!("TAP version 13\n1..{}", results.len());
+    for (idx, result) in results.iter().enumerate() {
+        match &result.failure {
+            None => write!(out, "\nok {} - {}", idx + 1, result.name).unwrap(),
+            Some(failure) => {
+                write!(out, "\nnot ok {} - {}", idx + 1, result.name).unwrap();
+                write!(out, "\n# {}", failure).unwrap();
+                for line in failure.details().lines() {
+                    write!(out, "\n# {}", line).unwrap();
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Escapes text for use in XML content and attribute values. Control
+/// characters XML cannot represent are replaced
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\r' => out.push_str("&#13;"),
+            '\n' | '\t' => out.push(c),
+            c if (c as u32) < 0x20 => out.push(char::REPLACEMENT_CHARACTER),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Formats the results as a JUnit XML report
+pub fn junit(results: &[TestResult]) -> String {
+    let (_, failed) = counts(results);
+    let time: f64 = results.iter().map(|x| x.duration.as_secs_f64()).sum();
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        out,
+        "<testsuites tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        results.len(),
+        failed,
+        time
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        SUITE_NAME,
+        results.len(),
+        failed,
+        time
+    )
+    .unwrap();
+
+    for result in results {
+        let name = xml_escape(&result.name);
+        let time = result.duration.as_secs_f64();
+        match &result.failure {
+            None => writeln!(
+                out,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"/>",
+                name, SUITE_NAME, time
+            )
+            .unwrap(),
+            Some(failure) => {
+                writeln!(
+                    out,
+                    "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">",
+                    name, SUITE_NAME, time
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "      <failure message=\"{}\">{}</failure>",
+                    xml_escape(&failure.to_string()),
+                    xml_escape(&failure.details())
+                )
+                .unwrap();
+                writeln!(out, "    </testcase>").unwrap();
+            }
+        }
+    }
+
+    out.push_str("  </testsuite>\n</testsuites>");
+    out
+}
@@ -1,3 +1,5 @@
+use crate::span::Pos;
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Token {
     Space,
@@ -9,6 +11,9 @@ pub struct Tokens<'a> {
     source: &'a [u8],
     idx: usize,
     line_no: usize,
+    column: usize,
+    /// The position of the most recently returned token
+    pos: Pos,
 }
 
 impl<'a> Tokens<'a> {
@@ -19,11 +24,24 @@ impl<'a> Tokens<'a> {
             source,
             idx: 0,
             line_no: 1,
+            column: 1,
+            pos: Pos::default(),
         }
     }
 
-    pub fn line_no(&self) -> usize {
-        self.line_no
+    /// Returns the position of the most recently returned token
+    pub fn pos(&self) -> Pos {
+        self.pos
+    }
+
+    /// Returns the position just past the most recently returned token,
+    /// or the end of the source if there are no tokens left
+    pub fn end_pos(&self) -> Pos {
+        Pos {
+            offset: self.idx,
+            line: self.line_no,
+            column: self.column,
+        }
     }
 }
 
@@ -34,18 +52,26 @@ impl<'a> Iterator for Tokens<'a> {
         loop {
             if self.source.get(self.idx).is_some() {
                 let b = self.source[self.idx];
+                self.pos = self.end_pos();
                 self.idx += 1;
 
+                // Continuation bytes of multibyte characters take up no columns
+                if b & 0xC0 != 0x80 {
+                    self.column += 1;
+                }
+
                 match b {
                     b' ' => return Some(Token::Space),
                     b'\t' => return Some(Token::Tab),
                     b'\n' => {
                         self.line_no += 1;
+                        self.column = 1;
                         return Some(Token::Newline);
                     }
                     _ => continue,
                 }
             } else {
+                self.pos = self.end_pos();
                 return None;
             }
         }
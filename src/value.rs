@@ -0,0 +1,336 @@
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
+#[cfg(feature = "bignum")]
+use num_integer::Integer;
+#[cfg(feature = "bignum")]
+use num_traits::{Signed, ToPrimitive, Zero};
+#[cfg(not(feature = "bignum"))]
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(not(feature = "bignum"))]
+type Repr = i64;
+#[cfg(feature = "bignum")]
+type Repr = BigInt;
+
+/// An integer manipulated by a Whitespace program
+///
+/// By default this is a 64-bit signed integer. With the `bignum` feature
+/// enabled it is an arbitrary precision integer, which matches the
+/// reference implementation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Value(Repr);
+
+/// The error returned when a string cannot be parsed as a `Value`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParseValueError;
+
+#[cfg(not(feature = "bignum"))]
+impl Value {
+    /// Builds a value from its sign and its binary digits, most significant
+    /// first. Returns `None` if the value does not fit
+    pub(crate) fn from_binary(negative: bool, digits: &[bool]) -> Option<Self> {
+        let mut num = 0i128;
+        for digit in digits {
+            num = num.checked_mul(2)?.checked_add(i128::from(*digit))?;
+        }
+
+        if negative {
+            num = -num;
+        }
+
+        i64::try_from(num).ok().map(Value)
+    }
+
+    /// Splits the value into its sign and its binary digits, most
+    /// significant first. Zero is represented by a single digit
+    pub(crate) fn to_binary(&self) -> (bool, Vec<bool>) {
+        let digits = format!("{:b}", self.0.unsigned_abs());
+        (
+            self.is_negative(),
+            digits.chars().map(|c| c == '1').collect(),
+        )
+    }
+
+    /// Returns true if the value is zero
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if the value is strictly less than zero
+    pub fn is_negative(&self) -> bool {
+        self.0.is_negative()
+    }
+
+    /// Converts the value to an `i64` if it fits
+    pub fn to_i64(&self) -> Option<i64> {
+        Some(self.0)
+    }
+
+    /// Converts the value to the character it represents when output,
+    /// keeping only its lowest byte
+    pub fn to_char(&self) -> char {
+        self.0 as u8 as char
+    }
+
+    /// Adds two values, returning `None` on overflow
+    pub fn checked_add(&self, rhs: &Value) -> Option<Value> {
+        self.0.checked_add(rhs.0).map(Value)
+    }
+
+    /// Adds two values, wrapping around on overflow
+    pub fn wrapping_add(&self, rhs: &Value) -> Value {
+        Value(self.0.wrapping_add(rhs.0))
+    }
+
+    /// Adds two values, clamping to the representable range on overflow
+    pub fn saturating_add(&self, rhs: &Value) -> Value {
+        Value(self.0.saturating_add(rhs.0))
+    }
+
+    /// Subtracts two values, returning `None` on overflow
+    pub fn checked_sub(&self, rhs: &Value) -> Option<Value> {
+        self.0.checked_sub(rhs.0).map(Value)
+    }
+
+    /// Subtracts two values, wrapping around on overflow
+    pub fn wrapping_sub(&self, rhs: &Value) -> Value {
+        Value(self.0.wrapping_sub(rhs.0))
+    }
+
+    /// Subtracts two values, clamping to the representable range on overflow
+    pub fn saturating_sub(&self, rhs: &Value) -> Value {
+        Value(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Multiplies two values, returning `None` on overflow
+    pub fn checked_mul(&self, rhs: &Value) -> Option<Value> {
+        self.0.checked_mul(rhs.0).map(Value)
+    }
+
+    /// Multiplies two values, wrapping around on overflow
+    pub fn wrapping_mul(&self, rhs: &Value) -> Value {
+        Value(self.0.wrapping_mul(rhs.0))
+    }
+
+    /// Multiplies two values, clamping to the representable range on overflow
+    pub fn saturating_mul(&self, rhs: &Value) -> Value {
+        Value(self.0.saturating_mul(rhs.0))
+    }
+
+    /// Divides two values, truncating towards zero. Returns `None` on
+    /// overflow or division by zero
+    pub fn checked_div(&self, rhs: &Value) -> Option<Value> {
+        self.0.checked_div(rhs.0).map(Value)
+    }
+
+    /// Divides two values, truncating towards zero and wrapping around on
+    /// overflow. Panics on division by zero
+    pub fn wrapping_div(&self, rhs: &Value) -> Value {
+        Value(self.0.wrapping_div(rhs.0))
+    }
+
+    /// Divides two values, truncating towards zero and clamping to the
+    /// representable range on overflow. Panics on division by zero
+    pub fn saturating_div(&self, rhs: &Value) -> Value {
+        Value(self.0.saturating_div(rhs.0))
+    }
+
+    /// Takes the remainder of dividing two values, with the sign of the
+    /// dividend. Returns `None` on division by zero. Unlike `i64`, this
+    /// gives `i64::MIN % -1` its exact remainder of zero instead of
+    /// overflowing
+    pub fn checked_rem(&self, rhs: &Value) -> Option<Value> {
+        if rhs.is_zero() {
+            None
+        } else {
+            Some(self.wrapping_rem(rhs))
+        }
+    }
+
+    /// Takes the remainder of dividing two values, with the sign of the
+    /// dividend. The only overflowing case, `i64::MIN % -1`, gives zero.
+    /// Panics on division by zero
+    pub fn wrapping_rem(&self, rhs: &Value) -> Value {
+        Value(self.0.wrapping_rem(rhs.0))
+    }
+
+    /// Same as `wrapping_rem`, since the exact remainder of the only
+    /// overflowing case is zero
+    pub fn saturating_rem(&self, rhs: &Value) -> Value {
+        self.wrapping_rem(rhs)
+    }
+}
+
+#[cfg(feature = "bignum")]
+impl Value {
+    /// Builds a value from its sign and its binary digits, most significant
+    /// first. Returns `None` if the value does not fit
+    pub(crate) fn from_binary(negative: bool, digits: &[bool]) -> Option<Self> {
+        let mut num = BigInt::zero();
+        for digit in digits {
+            num <<= 1;
+            if *digit {
+                num += 1;
+            }
+        }
+
+        if negative {
+            num = -num;
+        }
+
+        Some(Value(num))
+    }
+
+    /// Splits the value into its sign and its binary digits, most
+    /// significant first. Zero is represented by a single digit
+    pub(crate) fn to_binary(&self) -> (bool, Vec<bool>) {
+        let digits = format!("{:b}", self.0.magnitude());
+        (
+            self.is_negative(),
+            digits.chars().map(|c| c == '1').collect(),
+        )
+    }
+
+    /// Returns true if the value is zero
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    /// Returns true if the value is strictly less than zero
+    pub fn is_negative(&self) -> bool {
+        self.0.is_negative()
+    }
+
+    /// Converts the value to an `i64` if it fits
+    pub fn to_i64(&self) -> Option<i64> {
+        self.0.to_i64()
+    }
+
+    /// Converts the value to the character it represents when output,
+    /// keeping only its lowest byte
+    pub fn to_char(&self) -> char {
+        let byte = &self.0 & BigInt::from(0xff);
+        byte.to_u8().unwrap_or_default() as char
+    }
+
+    /// Adds two values. Never overflows
+    pub fn checked_add(&self, rhs: &Value) -> Option<Value> {
+        Some(self.wrapping_add(rhs))
+    }
+
+    /// Adds two values. Never overflows
+    pub fn wrapping_add(&self, rhs: &Value) -> Value {
+        Value(&self.0 + &rhs.0)
+    }
+
+    /// Adds two values. Never overflows
+    pub fn saturating_add(&self, rhs: &Value) -> Value {
+        self.wrapping_add(rhs)
+    }
+
+    /// Subtracts two values. Never overflows
+    pub fn checked_sub(&self, rhs: &Value) -> Option<Value> {
+        Some(self.wrapping_sub(rhs))
+    }
+
+    /// Subtracts two values. Never overflows
+    pub fn wrapping_sub(&self, rhs: &Value) -> Value {
+        Value(&self.0 - &rhs.0)
+    }
+
+    /// Subtracts two values. Never overflows
+    pub fn saturating_sub(&self, rhs: &Value) -> Value {
+        self.wrapping_sub(rhs)
+    }
+
+    /// Multiplies two values. Never overflows
+    pub fn checked_mul(&self, rhs: &Value) -> Option<Value> {
+        Some(self.wrapping_mul(rhs))
+    }
+
+    /// Multiplies two values. Never overflows
+    pub fn wrapping_mul(&self, rhs: &Value) -> Value {
+        Value(&self.0 * &rhs.0)
+    }
+
+    /// Multiplies two values. Never overflows
+    pub fn saturating_mul(&self, rhs: &Value) -> Value {
+        self.wrapping_mul(rhs)
+    }
+
+    /// Divides two values, rounding towards negative infinity like
+    /// Haskell's `div`. Returns `None` on division by zero
+    pub fn checked_div(&self, rhs: &Value) -> Option<Value> {
+        if rhs.is_zero() {
+            None
+        } else {
+            Some(self.wrapping_div(rhs))
+        }
+    }
+
+    /// Divides two values, rounding towards negative infinity like
+    /// Haskell's `div`. Panics on division by zero
+    pub fn wrapping_div(&self, rhs: &Value) -> Value {
+        Value(self.0.div_floor(&rhs.0))
+    }
+
+    /// Same as `wrapping_div`, since division never overflows
+    pub fn saturating_div(&self, rhs: &Value) -> Value {
+        self.wrapping_div(rhs)
+    }
+
+    /// Takes the remainder of dividing two values, with the sign of the
+    /// divisor like Haskell's `mod`. Returns `None` on division by zero
+    pub fn checked_rem(&self, rhs: &Value) -> Option<Value> {
+        if rhs.is_zero() {
+            None
+        } else {
+            Some(self.wrapping_rem(rhs))
+        }
+    }
+
+    /// Takes the remainder of dividing two values, with the sign of the
+    /// divisor like Haskell's `mod`. Panics on division by zero
+    pub fn wrapping_rem(&self, rhs: &Value) -> Value {
+        Value(self.0.mod_floor(&rhs.0))
+    }
+
+    /// Same as `wrapping_rem`, since the remainder never overflows
+    pub fn saturating_rem(&self, rhs: &Value) -> Value {
+        self.wrapping_rem(rhs)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(num: i64) -> Self {
+        Value(Repr::from(num))
+    }
+}
+
+impl FromStr for Value {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(x) => Ok(Value(x)),
+            Err(_) => Err(ParseValueError),
+        }
+    }
+}
+
+impl fmt::Display for ParseValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid integer literal")
+    }
+}
+
+impl Error for ParseValueError {}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
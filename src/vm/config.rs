@@ -0,0 +1,49 @@
+/// What the VM does when the result of an arithmetic instruction does not
+/// fit in a `Value`
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum OverflowPolicy {
+    /// Halt the program with `RuntimeError::Overflow`
+    #[default]
+    Trap,
+    /// Wrap around using two's complement arithmetic
+    Wrap,
+    /// Clamp the result to the smallest or largest representable value
+    Saturate,
+}
+
+/// What `ReadChar` and `ReadNum` do when the input has run out
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum EofPolicy {
+    /// Halt the program with `RuntimeError::IoError`
+    #[default]
+    Error,
+    /// Store 0, like reading a NUL byte
+    Zero,
+    /// Store -1, like C's `EOF`
+    MinusOne,
+}
+
+/// Options controlling how the VM runs a program
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// How arithmetic overflow is handled. Has no effect with the `bignum`
+    /// feature, where arithmetic never overflows
+    pub overflow: OverflowPolicy,
+    /// What reading past the end of the input does
+    pub eof: EofPolicy,
+    /// The maximum number of instructions to execute before halting with
+    /// `RuntimeError::FuelExhausted`, or `None` for no limit
+    pub fuel: Option<u64>,
+    /// The maximum number of values on the operand stack, or `None` for no
+    /// limit
+    pub max_stack: Option<usize>,
+    /// The maximum depth of the call stack, counting the main program, or
+    /// `None` for no limit
+    pub max_call_depth: Option<usize>,
+    /// The maximum number of distinct heap addresses that can be written to,
+    /// or `None` for no limit
+    pub max_heap: Option<usize>,
+    /// Whether running past the last instruction halts the program normally
+    /// instead of failing with `RuntimeError::MissingEnd`
+    pub implicit_end: bool,
+}
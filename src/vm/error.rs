@@ -1,61 +1,245 @@
-#[derive(Copy, Clone, Debug)]
+use crate::json::{self, Object};
+use crate::span::Span;
+use crate::value::Value;
+use std::error::Error;
+use std::fmt;
+
+/// What kind of error caused the program to halt
+#[derive(Clone, Debug, PartialEq)]
 pub enum RuntimeError {
     /// The program tried to divide by zero
     ZeroDivision,
-    /// The program tried to access an invalid heap location
-    InvalidHeapEntry,
+    /// The program tried to retrieve from the given heap address, which was
+    /// never stored to
+    InvalidHeapEntry(Value),
     /// The program had some unspecified IO error
     IoError,
     /// The program could not parse the user's input as a valid number
     NumParseError,
     /// The program tried to pop the stack while it was empty
     StackUnderflow,
+    /// The result of an arithmetic instruction did not fit in a `Value`
+    Overflow,
+    /// The program executed as many instructions as its budget allows
+    FuelExhausted,
+    /// The program tried to push a value onto a full stack
+    StackOverflow,
+    /// The program tried to call a subroutine with the call stack full
+    CallStackOverflow,
+    /// The program tried to write to a new heap address with the heap full
+    HeapLimitExceeded,
+    /// Execution ran past the last instruction without reaching an `End`
+    MissingEnd,
+    /// The program called a subroutine whose label was never marked. Only
+    /// the partial program of a source with parse errors can do this
+    UnresolvedCall,
 }
 
+impl RuntimeError {
+    /// Returns a stable identifier for the kind of error
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuntimeError::ZeroDivision => "zero_division",
+            RuntimeError::InvalidHeapEntry(_) => "invalid_heap_entry",
+            RuntimeError::IoError => "io_error",
+            RuntimeError::NumParseError => "num_parse_error",
+            RuntimeError::StackUnderflow => "stack_underflow",
+            RuntimeError::Overflow => "overflow",
+            RuntimeError::FuelExhausted => "fuel_exhausted",
+            RuntimeError::StackOverflow => "stack_overflow",
+            RuntimeError::CallStackOverflow => "call_stack_overflow",
+            RuntimeError::HeapLimitExceeded => "heap_limit_exceeded",
+            RuntimeError::MissingEnd => "missing_end",
+            RuntimeError::UnresolvedCall => "unresolved_call",
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::ZeroDivision => write!(f, "Attempted to divide by zero"),
+            RuntimeError::InvalidHeapEntry(addr) => {
+                write!(f, "Attempted to access invalid heap entry {}", addr)
+            }
+            RuntimeError::IoError => write!(f, "An unexpected IO error occurred."),
+            RuntimeError::NumParseError => write!(f, "Could not parse input as valid integer."),
+            RuntimeError::StackUnderflow => write!(f, "The program stack underflowed."),
+            RuntimeError::Overflow => write!(f, "Arithmetic overflow."),
+            RuntimeError::FuelExhausted => write!(f, "The instruction budget was exhausted."),
+            RuntimeError::StackOverflow => write!(f, "The program stack overflowed."),
+            RuntimeError::CallStackOverflow => write!(f, "The call stack overflowed."),
+            RuntimeError::HeapLimitExceeded => write!(f, "The heap size limit was exceeded."),
+            RuntimeError::MissingEnd => {
+                write!(f, "Execution ran off the end of the program.")
+            }
+            RuntimeError::UnresolvedCall => {
+                write!(f, "Called a subroutine whose label was never marked.")
+            }
+        }
+    }
+}
+
+impl Error for RuntimeError {}
+
+/// A single frame of a stack traceback
+#[derive(Debug)]
 pub struct TraceEntry {
-    line_no: usize,
+    span: Span,
     label: Option<usize>,
+    call_site: Option<Span>,
 }
 
 impl TraceEntry {
-    pub fn new(line_no: usize, label: Option<usize>) -> Self {
-        Self { line_no, label }
+    pub fn new(span: Span, label: Option<usize>, call_site: Option<Span>) -> Self {
+        Self {
+            span,
+            label,
+            call_site,
+        }
+    }
+
+    /// Returns the source line number the frame was executing
+    pub fn line_no(&self) -> usize {
+        self.span.start.line
+    }
+
+    /// Returns the span of source the frame was executing
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the label of the frame's subroutine, or `None` for main
+    pub fn label(&self) -> Option<usize> {
+        self.label
+    }
+
+    /// Returns the span of the call that entered the frame's subroutine, or
+    /// `None` for main
+    pub fn call_site(&self) -> Option<Span> {
+        self.call_site
+    }
+
+    /// Encodes the frame as a JSON object
+    pub fn to_json(&self) -> String {
+        Object::new()
+            .number("line", self.line_no())
+            .number("column", self.span.start.column)
+            .optional("label", self.label)
+            .raw("span", &json::span(self.span))
+            .raw(
+                "call_site",
+                &self.call_site.map_or("null".to_string(), json::span),
+            )
+            .finish()
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let start = self.span.start;
+        write!(f, "[Line {}:{}] in ", start.line, start.column)?;
+        match (self.label, self.call_site) {
+            (Some(label), Some(call_site)) => {
+                let call = call_site.start;
+                write!(
+                    f,
+                    "subroutine #{}, called from line {}:{}",
+                    label, call.line, call.column
+                )
+            }
+            (Some(label), None) => write!(f, "subroutine #{}", label),
+            (None, _) => write!(f, "main()"),
+        }
     }
 }
 
+/// The virtual call stack at the point a runtime error occurred,
+/// ordered from the outermost frame to the innermost
+#[derive(Debug)]
 pub struct Traceback {
     pub stack: Vec<TraceEntry>,
     pub reason: RuntimeError,
+    /// The program counter of the instruction that failed
+    pub pc: usize,
+    /// The instruction that failed written as assembly, or `None` if
+    /// execution ran past the end of the program
+    pub instruction: Option<String>,
+    /// The values at the top of the operand stack, with the top value last.
+    /// Instructions that fail leave their operands on the stack
+    pub stack_top: Vec<Value>,
+    /// The total number of values on the operand stack
+    pub stack_depth: usize,
 }
 
 impl Traceback {
-    /// Prints the traceback to stdout
+    /// Prints the traceback to stderr
     pub fn dump(&self) {
-        println!("Stack traceback:");
+        eprintln!("{}", self);
+    }
+
+    /// Encodes the traceback as a single line JSON object, with the frames
+    /// ordered from the outermost to the innermost
+    pub fn to_json(&self) -> String {
+        let frames = json::array(self.stack.iter().map(TraceEntry::to_json));
+        let stack_top = json::array(self.stack_top.iter().map(Value::to_string));
+        let instruction = self
+            .instruction
+            .as_deref()
+            .map_or("null".to_string(), json::string);
+
+        let mut object = Object::new()
+            .string("type", "runtime_error")
+            .string("reason", self.reason.name());
+        if let RuntimeError::InvalidHeapEntry(addr) = &self.reason {
+            object = object.number("address", addr);
+        }
+
+        object
+            .string("message", &self.reason.to_string())
+            .number("pc", self.pc)
+            .raw("instruction", &instruction)
+            .raw("stack_top", &stack_top)
+            .number("stack_depth", self.stack_depth)
+            .raw("frames", &frames)
+            .finish()
+    }
+}
+
+impl fmt::Display for Traceback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Stack traceback:")?;
         for entry in &self.stack {
-            if let Some(label) = entry.label {
-                println!("[Line {}] in subroutine #{}", entry.line_no, label);
-            } else {
-                println!("[Line {}] in main()", entry.line_no);
-            }
+            writeln!(f, "{}", entry)?;
         }
 
-        match self.reason {
-            RuntimeError::ZeroDivision => {
-                println!("Error: Attempted to divide by zero");
-            }
-            RuntimeError::InvalidHeapEntry => {
-                println!("Error: Attempted to access invalid heap entry");
-            }
-            RuntimeError::IoError => {
-                println!("Error: An unexpected IO error occurred.");
-            }
-            RuntimeError::NumParseError => {
-                println!("Error: Could not parse input as valid integer.");
-            }
-            RuntimeError::StackUnderflow => {
-                println!("Error: The program stack underflowed.");
-            }
+        writeln!(f, "Error: {}", self.reason)?;
+        match &self.instruction {
+            Some(instruction) => writeln!(f, "Instruction: {:04}  {}", self.pc, instruction)?,
+            None => writeln!(f, "Instruction: {:04}  <end of program>", self.pc)?,
         }
+
+        if self.stack_top.is_empty() {
+            return write!(f, "Stack: empty");
+        }
+
+        write!(f, "Stack (top last):")?;
+        let omitted = self.stack_depth - self.stack_top.len();
+        if omitted > 0 {
+            write!(f, " ...")?;
+        }
+        for value in &self.stack_top {
+            write!(f, " {}", value)?;
+        }
+        if omitted > 0 {
+            write!(f, " ({} values in total)", self.stack_depth)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Traceback {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.reason)
     }
 }
@@ -1,241 +1,749 @@
-use self::error::{RuntimeError, TraceEntry, Traceback};
-use self::frame::CallFrame;
+pub use self::config::{Config, EofPolicy, OverflowPolicy};
+pub use self::error::{RuntimeError, TraceEntry, Traceback};
+pub use self::frame::CallFrame;
+pub use self::outcome::Outcome;
+pub use self::profile::{Profile, SubroutineStats};
+use self::trace::{Effect, Record};
+pub use self::trace::{TraceFilter, TraceFormat, Tracer};
+use crate::asm::disasm;
 use crate::program::{Instruction, Program};
+use crate::span::Span;
+use crate::value::Value;
 use std::collections::HashMap;
-use std::io::{self, Read, Write};
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
 
+mod config;
 mod error;
 mod frame;
+mod outcome;
+mod profile;
+mod trace;
 
 /// The result of running the VM on a given program
-type VmResult<T> = Result<T, Traceback>;
+pub type VmResult<T> = Result<T, Traceback>;
+
+/// How many values from the top of the stack a `Traceback` records
+const TRACEBACK_VALUES: usize = 8;
 
 /// The virtual machine running the program
 pub struct Vm<'a> {
-    stack: Vec<i64>,
+    stack: Vec<Value>,
     call_stack: Vec<CallFrame>,
-    heap: HashMap<i64, i64>,
+    heap: HashMap<Value, Value>,
     program: &'a Program,
+    config: Config,
+    /// Where the program reads its input from
+    input: Box<dyn BufRead + 'a>,
+    /// Where the program writes its output to
+    output: Box<dyn Write + 'a>,
+    /// Part of a line of input read by `ReadNum` before the rest of the line
+    /// was ready
+    pending_input: Vec<u8>,
+    /// The number of instructions executed so far
+    executed: u64,
+    /// The program counter of the instruction being executed
+    inst_pc: usize,
+    /// Whether the program has stopped running, either normally or due to
+    /// an error
+    halted: bool,
+    /// Logs executed instructions, if tracing is enabled
+    tracer: Option<Tracer<'a>>,
+    /// What the instruction being traced did besides changing the stack, or
+    /// `None` if it is not being traced
+    effects: Option<Vec<Effect>>,
+    /// Gathers execution counts and timings, if profiling is enabled
+    profile: Option<&'a mut Profile>,
+}
+
+/// The result of reading from the program's input
+enum Input<T> {
+    Data(T),
+    /// The input has run out
+    Eof,
+    /// The input is not ready yet
+    Pending,
+}
+
+/// The state of the VM after executing an instruction
+#[derive(Debug)]
+pub enum Status {
+    /// The program can keep running
+    Continue,
+    /// The program has finished running
+    Halted,
+    /// The program is waiting for input that is not ready yet. Stepping
+    /// again retries the instruction
+    NeedsInput,
+    /// The program stopped due to a runtime error
+    Error(Traceback),
 }
 
 impl<'a> Vm<'a> {
-    /// Constructs a new VM to run the given program
+    /// Constructs a new VM to run the given program, reading from stdin
+    /// and writing to stdout
     pub fn new(program: &'a Program) -> Self {
+        Self::with_io(program, io::stdin().lock(), io::stdout())
+    }
+
+    /// Constructs a new VM to run the given program, reading from `input`
+    /// and writing to `output`
+    pub fn with_io<R, W>(program: &'a Program, input: R, output: W) -> Self
+    where
+        R: BufRead + 'a,
+        W: Write + 'a,
+    {
         Self {
             stack: vec![],
-            call_stack: vec![],
+            call_stack: vec![CallFrame::new_main()],
             heap: HashMap::new(),
             program,
+            config: Config::default(),
+            input: Box::new(input),
+            output: Box::new(output),
+            pending_input: vec![],
+            executed: 0,
+            inst_pc: 0,
+            halted: false,
+            tracer: None,
+            effects: None,
+            profile: None,
+        }
+    }
+
+    /// Sets the options the VM runs the program with
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Logs every executed instruction that passes the tracer's filter. If
+    /// writing to the tracer's output fails, tracing stops and the program
+    /// keeps running, so the host should check its writer for errors
+    pub fn with_tracer(mut self, tracer: Tracer<'a>) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Records execution counts and timings in `profile` while the program
+    /// runs
+    pub fn with_profile(mut self, profile: &'a mut Profile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Returns the span of the instruction at `pc`, or of the last
+    /// instruction if `pc` is past the end of the program
+    fn span_at(&self, pc: usize) -> Span {
+        match self.program.inst_count() {
+            0 => Span::default(),
+            count => self.program.span_at(pc.min(count - 1)),
         }
     }
 
     /// Raises a runtime error
     fn runtime_error(&self, reason: RuntimeError) -> Traceback {
+        // The innermost frame is at the failing instruction, and every other
+        // frame has already moved past the call it is waiting on
+        let depth = self.call_stack.len();
+        let locations: Vec<_> = self
+            .call_stack
+            .iter()
+            .enumerate()
+            .map(|(idx, frame)| {
+                if idx + 1 == depth {
+                    self.inst_pc
+                } else {
+                    frame.pc - 1
+                }
+            })
+            .collect();
+
         let mut stack = vec![];
-        for frame in &self.call_stack {
-            let CallFrame { pc, label } = frame;
-            let line_no = self.program.line_at(*pc);
-            let entry = TraceEntry::new(line_no, *label);
+        for (idx, frame) in self.call_stack.iter().enumerate() {
+            let span = self.span_at(locations[idx]);
+            let call_site = idx.checked_sub(1).map(|x| self.span_at(locations[x]));
+            let entry = TraceEntry::new(span, frame.label, call_site);
             stack.push(entry);
         }
 
-        Traceback { stack, reason }
+        let instruction = self.program.instructions().get(self.inst_pc);
+        let instruction = instruction.map(|x| disasm::mnemonic(self.program, x));
+        let skip = self.stack.len().saturating_sub(TRACEBACK_VALUES);
+
+        Traceback {
+            stack,
+            reason,
+            pc: self.inst_pc,
+            instruction,
+            stack_top: self.stack[skip..].to_vec(),
+            stack_depth: self.stack.len(),
+        }
     }
 
-    fn push(&mut self, value: i64) {
+    fn push(&mut self, value: Value) -> VmResult<()> {
+        if let Some(max) = self.config.max_stack {
+            if self.stack.len() >= max {
+                return Err(self.runtime_error(RuntimeError::StackOverflow));
+            }
+        }
+
         self.stack.push(value);
+        Ok(())
+    }
+
+    /// Writes a value to the heap
+    fn store(&mut self, addr: Value, value: Value) -> VmResult<()> {
+        if let Some(max) = self.config.max_heap {
+            if self.heap.len() >= max && !self.heap.contains_key(&addr) {
+                return Err(self.runtime_error(RuntimeError::HeapLimitExceeded));
+            }
+        }
+
+        self.record(|| Effect::Store(addr.clone(), value.clone()));
+        self.heap.insert(addr, value);
+        Ok(())
     }
 
-    fn pop(&mut self) -> VmResult<i64> {
+    /// Notes something the instruction being traced did, if any
+    fn record<F: FnOnce() -> Effect>(&mut self, effect: F) {
+        if let Some(effects) = &mut self.effects {
+            effects.push(effect());
+        }
+    }
+
+    fn pop(&mut self) -> VmResult<Value> {
         match self.stack.pop() {
             Some(x) => Ok(x),
             None => Err(self.runtime_error(RuntimeError::StackUnderflow)),
         }
     }
 
-    fn peek(&self) -> VmResult<i64> {
+    fn peek(&self) -> VmResult<Value> {
         match self.stack.last() {
-            Some(x) => Ok(*x),
+            Some(x) => Ok(x.clone()),
             None => Err(self.runtime_error(RuntimeError::StackUnderflow)),
         }
     }
 
-    fn current_frame(&mut self) -> &mut CallFrame {
-        self.call_stack.last_mut().unwrap()
+    /// Returns the top two values of the stack without popping them, with
+    /// the top value second. Instructions check their operands this way
+    /// before popping them, so the stack is left as it was if they fail
+    fn peek_two(&self) -> VmResult<(&Value, &Value)> {
+        match self.stack.as_slice() {
+            [.., left, right] => Ok((left, right)),
+            _ => Err(self.runtime_error(RuntimeError::StackUnderflow)),
+        }
+    }
+
+    /// Replaces the top two values of the stack with `value`
+    fn replace_two(&mut self, value: Value) {
+        self.stack.truncate(self.stack.len() - 2);
+        self.stack.push(value);
+    }
+
+    /// Combines two operands using the variant of an arithmetic operation
+    /// selected by the overflow policy
+    fn arith(
+        &self,
+        left: &Value,
+        right: &Value,
+        checked: fn(&Value, &Value) -> Option<Value>,
+        wrapping: fn(&Value, &Value) -> Value,
+        saturating: fn(&Value, &Value) -> Value,
+    ) -> VmResult<Value> {
+        match self.config.overflow {
+            OverflowPolicy::Trap => match checked(left, right) {
+                Some(x) => Ok(x),
+                None => Err(self.runtime_error(RuntimeError::Overflow)),
+            },
+            OverflowPolicy::Wrap => Ok(wrapping(left, right)),
+            OverflowPolicy::Saturate => Ok(saturating(left, right)),
+        }
     }
 
-    /// Runs the given `Program`
-    pub fn run(mut self) -> VmResult<()> {
-        let main_frame = CallFrame::new_main();
-        self.call_stack.push(main_frame);
+    /// Returns the program being run
+    pub fn program(&self) -> &'a Program {
+        self.program
+    }
+
+    /// Returns the program counter of the next instruction to execute
+    pub fn pc(&self) -> usize {
+        self.call_stack.last().map_or(0, |frame| frame.pc)
+    }
 
+    /// Returns the operand stack, with the top of the stack last
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    /// Returns a mutable reference to the operand stack
+    pub fn stack_mut(&mut self) -> &mut Vec<Value> {
+        &mut self.stack
+    }
+
+    /// Returns the heap
+    pub fn heap(&self) -> &HashMap<Value, Value> {
+        &self.heap
+    }
+
+    /// Returns a mutable reference to the heap
+    pub fn heap_mut(&mut self) -> &mut HashMap<Value, Value> {
+        &mut self.heap
+    }
+
+    /// Returns the virtual call stack, with the innermost frame last
+    pub fn call_stack(&self) -> &[CallFrame] {
+        &self.call_stack
+    }
+
+    /// Returns the number of instructions executed so far
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Returns true if the program has stopped running
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Reads a single byte of input
+    fn read_byte(&mut self) -> VmResult<Input<u8>> {
         loop {
-            let pc = self.current_frame().pc;
-            let inst = self.program.inst_at(pc);
-            self.current_frame().pc += 1;
-
-            match inst {
-                Instruction::Push(idx) => {
-                    let constant = self.program.get_const(*idx);
-                    self.push(constant);
+            match self.input.fill_buf() {
+                Ok([]) => return Ok(Input::Eof),
+                Ok(buf) => {
+                    let c = buf[0];
+                    self.input.consume(1);
+                    return Ok(Input::Data(c));
                 }
-                Instruction::Dup => {
-                    let last = self.peek()?;
-                    self.push(last);
-                }
-                Instruction::Copy(idx) => {
-                    let idx = *idx as usize;
-                    if self.stack.len() < idx {
-                        return Err(self.runtime_error(RuntimeError::StackUnderflow));
-                    }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Input::Pending),
+                Err(_) => return Err(self.runtime_error(RuntimeError::IoError)),
+            }
+        }
+    }
 
-                    let idx = self.stack.len() - 1 - idx;
-                    let value = self.stack[idx];
-                    self.push(value);
-                }
-                Instruction::Swap => {
-                    if self.stack.len() < 2 {
-                        return Err(self.runtime_error(RuntimeError::StackUnderflow));
-                    }
+    /// Reads a line of input, including its line terminator. If a full line
+    /// is not ready yet, what was read so far is kept for the next attempt
+    fn read_line(&mut self) -> VmResult<Input<String>> {
+        match self.input.read_until(b'\n', &mut self.pending_input) {
+            Ok(_) if self.pending_input.is_empty() => return Ok(Input::Eof),
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Input::Pending),
+            Err(_) => return Err(self.runtime_error(RuntimeError::IoError)),
+        }
 
-                    let first = self.stack.len() - 1;
-                    let second = first - 1;
-                    self.stack.swap(first, second);
-                }
-                Instruction::Pop => {
-                    self.pop()?;
-                }
-                Instruction::Slide(idx) => {
-                    let idx = *idx as usize;
-                    if self.stack.len() < idx + 1 {
-                        return Err(self.runtime_error(RuntimeError::StackUnderflow));
-                    }
+        let line = std::mem::take(&mut self.pending_input);
+        match String::from_utf8(line) {
+            Ok(x) => Ok(Input::Data(x)),
+            Err(_) => Err(self.runtime_error(RuntimeError::IoError)),
+        }
+    }
 
-                    let last = self.pop()?;
-                    let new_len = self.stack.len() - idx;
-                    self.stack.truncate(new_len);
-                    self.push(last);
-                }
-                Instruction::Add => {
-                    let right = self.pop()?;
-                    let left = self.pop()?;
-                    let result = left + right;
-                    self.push(result);
-                }
-                Instruction::Subtract => {
-                    let right = self.pop()?;
-                    let left = self.pop()?;
-                    let result = left - right;
-                    self.push(result);
-                }
-                Instruction::Multiply => {
-                    let right = self.pop()?;
-                    let left = self.pop()?;
-                    let result = left * right;
-                    self.push(result);
-                }
-                Instruction::Divide => {
-                    let right = self.pop()?;
-                    if right == 0 {
-                        return Err(self.runtime_error(RuntimeError::ZeroDivision));
-                    }
+    /// Returns the value read at the end of the input, according to the
+    /// configured `EofPolicy`
+    fn eof_value(&self) -> VmResult<Value> {
+        match self.config.eof {
+            EofPolicy::Error => Err(self.runtime_error(RuntimeError::IoError)),
+            EofPolicy::Zero => Ok(Value::from(0)),
+            EofPolicy::MinusOne => Ok(Value::from(-1)),
+        }
+    }
 
-                    let left = self.pop()?;
-                    let result = left / right;
-                    self.push(result);
-                }
-                Instruction::Modulo => {
-                    let right = self.pop()?;
-                    if right == 0 {
-                        return Err(self.runtime_error(RuntimeError::ZeroDivision));
-                    }
+    /// Returns the index of the value `n` places below the top of the
+    /// stack, or `None` if there is no such value
+    fn stack_offset(&self, n: i64) -> Option<usize> {
+        let n = usize::try_from(n).ok()?;
+        self.stack.len().checked_sub(n)?.checked_sub(1)
+    }
 
-                    let left = self.pop()?;
-                    let result = left % right;
-                    self.push(result);
+    fn current_frame(&mut self) -> &mut CallFrame {
+        self.call_stack.last_mut().unwrap()
+    }
+
+    /// Consumes the VM and packages up the current state of its stack and
+    /// heap
+    pub fn into_outcome(self) -> Outcome {
+        Outcome {
+            stack: self.stack,
+            heap: self.heap,
+            instructions: self.executed,
+        }
+    }
+
+    /// Executes the next instruction. Never returns `Status::Error`; errors
+    /// are returned as a `Traceback` instead
+    fn execute(&mut self) -> VmResult<Status> {
+        let pc = self.current_frame().pc;
+        if pc >= self.program.inst_count() {
+            if self.config.implicit_end {
+                return Ok(Status::Halted);
+            }
+            return Err(self.runtime_error(RuntimeError::MissingEnd));
+        }
+
+        let inst = self.program.inst_at(pc);
+        self.current_frame().pc += 1;
+
+        match inst {
+            Instruction::Push(idx) => {
+                let constant = self.program.get_const(*idx).clone();
+                self.push(constant)?;
+            }
+            Instruction::Dup => {
+                let last = self.peek()?;
+                self.push(last)?;
+            }
+            Instruction::Copy(n) => {
+                // A negative operand is a parse error, but it can still
+                // reach the VM through the partial program `parse_all`
+                // returns, so it reaches past the bottom of the stack too
+                let idx = match self.stack_offset(*n) {
+                    Some(x) => x,
+                    None => return Err(self.runtime_error(RuntimeError::StackUnderflow)),
+                };
+                let value = self.stack[idx].clone();
+                self.push(value)?;
+            }
+            Instruction::Swap => {
+                if self.stack.len() < 2 {
+                    return Err(self.runtime_error(RuntimeError::StackUnderflow));
                 }
-                Instruction::Store => {
-                    let value = self.pop()?;
-                    let addr = self.pop()?;
-                    self.heap.insert(addr, value);
+
+                let first = self.stack.len() - 1;
+                let second = first - 1;
+                self.stack.swap(first, second);
+            }
+            Instruction::Pop => {
+                self.pop()?;
+            }
+            Instruction::Slide(n) => {
+                let new_len = match self.stack_offset(*n) {
+                    Some(x) => x,
+                    None => return Err(self.runtime_error(RuntimeError::StackUnderflow)),
+                };
+                let last = self.pop()?;
+                self.stack.truncate(new_len);
+                self.push(last)?;
+            }
+            Instruction::Add => {
+                let (left, right) = self.peek_two()?;
+                let result = self.arith(
+                    left,
+                    right,
+                    Value::checked_add,
+                    Value::wrapping_add,
+                    Value::saturating_add,
+                )?;
+                self.replace_two(result);
+            }
+            Instruction::Subtract => {
+                let (left, right) = self.peek_two()?;
+                let result = self.arith(
+                    left,
+                    right,
+                    Value::checked_sub,
+                    Value::wrapping_sub,
+                    Value::saturating_sub,
+                )?;
+                self.replace_two(result);
+            }
+            Instruction::Multiply => {
+                let (left, right) = self.peek_two()?;
+                let result = self.arith(
+                    left,
+                    right,
+                    Value::checked_mul,
+                    Value::wrapping_mul,
+                    Value::saturating_mul,
+                )?;
+                self.replace_two(result);
+            }
+            Instruction::Divide => {
+                let (left, right) = self.peek_two()?;
+                if right.is_zero() {
+                    return Err(self.runtime_error(RuntimeError::ZeroDivision));
                 }
-                Instruction::Retrieve => {
-                    let addr = self.pop()?;
-                    let value = match self.heap.get(&addr) {
-                        Some(x) => *x,
-                        None => return Err(self.runtime_error(RuntimeError::InvalidHeapEntry)),
-                    };
-                    self.push(value);
+
+                let result = self.arith(
+                    left,
+                    right,
+                    Value::checked_div,
+                    Value::wrapping_div,
+                    Value::saturating_div,
+                )?;
+                self.replace_two(result);
+            }
+            Instruction::Modulo => {
+                let (left, right) = self.peek_two()?;
+                if right.is_zero() {
+                    return Err(self.runtime_error(RuntimeError::ZeroDivision));
                 }
-                Instruction::Call(pc) => {
-                    let label = self.program.get_label(*pc).unwrap();
-                    let frame = CallFrame::new(*pc, label);
-                    self.call_stack.push(frame);
+
+                let result = self.arith(
+                    left,
+                    right,
+                    Value::checked_rem,
+                    Value::wrapping_rem,
+                    Value::saturating_rem,
+                )?;
+                self.replace_two(result);
+            }
+            Instruction::Store => {
+                let (addr, value) = self.peek_two()?;
+                let (addr, value) = (addr.clone(), value.clone());
+                self.store(addr, value)?;
+                self.stack.truncate(self.stack.len() - 2);
+            }
+            Instruction::Retrieve => {
+                let addr = self.peek()?;
+                let value = match self.heap.get(&addr) {
+                    Some(x) => x.clone(),
+                    None => {
+                        let error = RuntimeError::InvalidHeapEntry(addr);
+                        return Err(self.runtime_error(error));
+                    }
+                };
+                *self.stack.last_mut().unwrap() = value;
+            }
+            Instruction::Call(pc) => {
+                let label = match self.program.get_label(*pc) {
+                    Some(x) => x,
+                    None => return Err(self.runtime_error(RuntimeError::UnresolvedCall)),
+                };
+                if let Some(max) = self.config.max_call_depth {
+                    if self.call_stack.len() >= max {
+                        return Err(self.runtime_error(RuntimeError::CallStackOverflow));
+                    }
                 }
-                Instruction::Jump(pc) => {
+
+                let frame = CallFrame::new(*pc, label);
+                self.call_stack.push(frame);
+            }
+            Instruction::Jump(pc) => {
+                self.current_frame().pc = *pc;
+            }
+            Instruction::JumpIfZero(pc) => {
+                let cond = self.pop()?;
+                if cond.is_zero() {
                     self.current_frame().pc = *pc;
                 }
-                Instruction::JumpIfZero(pc) => {
-                    let cond = self.pop()?;
-                    if cond == 0 {
-                        self.current_frame().pc = *pc;
-                    }
+            }
+            Instruction::JumpIfNeg(pc) => {
+                let cond = self.pop()?;
+                if cond.is_negative() {
+                    self.current_frame().pc = *pc;
                 }
-                Instruction::JumpIfNeg(pc) => {
-                    let cond = self.pop()?;
-                    if cond.is_negative() {
-                        self.current_frame().pc = *pc;
-                    }
+            }
+            Instruction::Return => {
+                self.call_stack.pop();
+                if self.call_stack.is_empty() {
+                    return Ok(Status::Halted);
                 }
-                Instruction::Return => {
-                    self.call_stack.pop();
-                    if self.call_stack.is_empty() {
-                        return Ok(());
-                    }
+            }
+            Instruction::End => {
+                return Ok(Status::Halted);
+            }
+            Instruction::OutputChar => {
+                let c = self.pop()?.to_char();
+                self.record(|| Effect::Output(c.to_string()));
+                if write!(self.output, "{}", c)
+                    .and_then(|_| self.output.flush())
+                    .is_err()
+                {
+                    return Err(self.runtime_error(RuntimeError::IoError));
                 }
-                Instruction::End => {
-                    return Ok(());
+            }
+            Instruction::OutputNum => {
+                let num = self.pop()?;
+                self.record(|| Effect::Output(num.to_string()));
+                if write!(self.output, "{}", num)
+                    .and_then(|_| self.output.flush())
+                    .is_err()
+                {
+                    return Err(self.runtime_error(RuntimeError::IoError));
                 }
-                Instruction::OutputChar => {
-                    let c = self.pop()? as u8 as char;
-                    print!("{}", c);
-                    if io::stdout().flush().is_err() {
-                        return Err(self.runtime_error(RuntimeError::IoError));
+            }
+            Instruction::ReadChar => {
+                let addr = self.peek()?;
+                let c = match self.read_byte()? {
+                    Input::Data(x) => {
+                        self.record(|| Effect::Input(char::from(x).to_string()));
+                        Value::from(i64::from(x))
                     }
-                }
-                Instruction::OutputNum => {
-                    let num = self.pop()?;
-                    print!("{}", num);
-                    if io::stdout().flush().is_err() {
-                        return Err(self.runtime_error(RuntimeError::IoError));
+                    Input::Eof => self.eof_value()?,
+                    Input::Pending => {
+                        self.current_frame().pc = pc;
+                        return Ok(Status::NeedsInput);
                     }
-                }
-                Instruction::ReadChar => {
-                    let addr = self.pop()?;
+                };
 
-                    let mut c = [0u8];
-                    if io::stdin().read_exact(&mut c).is_err() {
-                        return Err(self.runtime_error(RuntimeError::IoError));
+                self.store(addr, c)?;
+                self.stack.pop();
+            }
+            Instruction::ReadNum => {
+                let addr = self.peek()?;
+                let num = match self.read_line()? {
+                    Input::Data(line) => {
+                        self.record(|| Effect::Input(line.clone()));
+                        match line.trim_end().parse() {
+                            Ok(x) => x,
+                            Err(_) => return Err(self.runtime_error(RuntimeError::NumParseError)),
+                        }
                     }
-                    self.heap.insert(addr, i64::from(c[0]));
-                }
-                Instruction::ReadNum => {
-                    let addr = self.pop()?;
-
-                    let mut num = String::new();
-                    if io::stdin().read_line(&mut num).is_err() {
-                        return Err(self.runtime_error(RuntimeError::IoError));
+                    Input::Eof => self.eof_value()?,
+                    Input::Pending => {
+                        self.current_frame().pc = pc;
+                        return Ok(Status::NeedsInput);
                     }
-                    let len = num.trim_end().len();
-                    num.truncate(len);
-                    let num: i64 = match num.parse() {
-                        Ok(x) => x,
-                        Err(_) => return Err(self.runtime_error(RuntimeError::NumParseError)),
-                    };
-
-                    self.heap.insert(addr, num);
+                };
+
+                self.store(addr, num)?;
+                self.stack.pop();
+            }
+        }
+
+        Ok(Status::Continue)
+    }
+
+    /// Starts tracing the instruction about to be executed if the tracer's
+    /// filter allows it
+    fn trace_start(&mut self) -> Option<Record> {
+        let tracer = self.tracer.as_ref()?;
+        let pc = self.inst_pc;
+        if pc >= self.program.inst_count()
+            || !tracer.traces(self.program.line_at(pc), &self.call_stack)
+        {
+            return None;
+        }
+
+        self.effects = Some(vec![]);
+        let span = self.program.span_at(pc);
+        Some(Record {
+            step: self.executed,
+            pc,
+            line: span.start.line,
+            column: span.start.column,
+            instruction: disasm::mnemonic(self.program, self.program.inst_at(pc)),
+            label: self.call_stack.last().and_then(|x| x.label),
+            before: trace::stack_top(&self.stack),
+            after: (vec![], 0),
+            effects: vec![],
+            error: None,
+        })
+    }
+
+    /// Logs the traced instruction once it has executed. Instructions
+    /// waiting for input are logged when they are retried
+    fn trace_finish(&mut self, mut record: Record, status: &VmResult<Status>) {
+        let effects = self.effects.take().unwrap_or_default();
+        if matches!(status, Ok(Status::NeedsInput)) {
+            return;
+        }
+
+        record.after = trace::stack_top(&self.stack);
+        record.effects = effects;
+        record.error = status.as_ref().err().map(|x| x.reason.name());
+
+        // The trace is the host's output rather than the program's, so
+        // failing to write it is not a runtime error
+        let tracer = self.tracer.as_mut().unwrap();
+        if tracer.write(&record).is_err() {
+            self.tracer = None;
+        }
+    }
+
+    /// Executes a single instruction.
+    ///
+    /// If the program tries to read input while its reader reports
+    /// `io::ErrorKind::WouldBlock`, the instruction is not executed and
+    /// `Status::NeedsInput` is returned, so the host can supply more input
+    /// and step again. Once the program halts, every further step returns
+    /// `Status::Halted`. If the configured fuel has run out, the program
+    /// halts with `RuntimeError::FuelExhausted` before executing anything.
+    pub fn step(&mut self) -> Status {
+        if self.halted {
+            return Status::Halted;
+        }
+
+        self.inst_pc = self.call_stack.last().map_or(0, |x| x.pc);
+        if let Some(fuel) = self.config.fuel {
+            if self.executed >= fuel {
+                self.halt();
+                return Status::Error(self.runtime_error(RuntimeError::FuelExhausted));
+            }
+        }
+
+        if let Some(profile) = &mut self.profile {
+            profile.start();
+        }
+        let depth = self.call_stack.len();
+        let record = self.trace_start();
+        let status = self.execute();
+        if let Some(record) = record {
+            self.trace_finish(record, &status);
+        }
+
+        if !matches!(status, Ok(Status::NeedsInput)) {
+            self.executed += 1;
+            if let Some(profile) = &mut self.profile {
+                if self.inst_pc < self.program.inst_count() {
+                    profile.record(self.inst_pc, depth, &self.call_stack);
                 }
             }
         }
+
+        match status {
+            Ok(Status::Halted) => {
+                self.halt();
+                Status::Halted
+            }
+            Ok(status) => status,
+            Err(traceback) => {
+                self.halt();
+                Status::Error(traceback)
+            }
+        }
+    }
+
+    /// Stops the program from running any further
+    fn halt(&mut self) {
+        self.halted = true;
+        if let Some(profile) = &mut self.profile {
+            profile.finish();
+        }
+    }
+
+    /// Executes at most `count` instructions, stopping early if the program
+    /// halts or needs input. Returns `Status::Continue` if all `count`
+    /// instructions were executed without the program stopping
+    pub fn run_for(&mut self, count: u64) -> Status {
+        for _ in 0..count {
+            match self.step() {
+                Status::Continue => {}
+                status => return status,
+            }
+        }
+
+        if self.halted {
+            Status::Halted
+        } else {
+            Status::Continue
+        }
+    }
+
+    /// Runs the given `Program` until it halts, returning the final state
+    /// of the stack and heap. Input that is not ready is treated as an IO
+    /// error
+    pub fn run(mut self) -> VmResult<Outcome> {
+        loop {
+            match self.step() {
+                Status::Continue => {}
+                Status::Halted => return Ok(self.into_outcome()),
+                Status::NeedsInput => return Err(self.runtime_error(RuntimeError::IoError)),
+                Status::Error(traceback) => return Err(traceback),
+            }
+        }
     }
 }
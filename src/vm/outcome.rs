@@ -0,0 +1,13 @@
+use crate::value::Value;
+use std::collections::HashMap;
+
+/// The final state of a program that ran to completion
+#[derive(Debug)]
+pub struct Outcome {
+    /// The values left on the stack when the program halted
+    pub stack: Vec<Value>,
+    /// The contents of the heap when the program halted
+    pub heap: HashMap<Value, Value>,
+    /// The number of instructions executed
+    pub instructions: u64,
+}
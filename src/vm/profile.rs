@@ -0,0 +1,441 @@
+use crate::asm::disasm::{label_name, mnemonic};
+use crate::json::{self, Object};
+use crate::program::Program;
+use crate::vm::CallFrame;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// How many of the hottest lines and instructions a report lists
+const REPORT_ROWS: usize = 20;
+
+/// What a profile knows about a subroutine, or about the main program
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SubroutineStats {
+    /// How many times the subroutine was called
+    pub calls: u64,
+    /// The instructions executed in the subroutine itself
+    pub exclusive: u64,
+    /// The instructions executed in the subroutine and everything it called.
+    /// Recursive calls are only counted once
+    pub inclusive: u64,
+    /// The time spent in the subroutine itself
+    pub self_time: Duration,
+    /// The time spent in the subroutine and everything it called
+    pub total_time: Duration,
+}
+
+/// A subroutine that has been entered and has not returned yet
+struct OpenFrame {
+    label: Option<usize>,
+    /// How many instructions had been executed when it was entered
+    executed: u64,
+    /// How many instructions it has executed itself
+    exclusive: u64,
+    entered: Instant,
+    /// The index of the call stack it runs with in `Profile::stacks`
+    stack: usize,
+}
+
+/// A distinct call stack the program ran with. Together they form a tree
+/// rooted at the main program
+struct StackNode {
+    parent: Option<usize>,
+    label: Option<usize>,
+    /// How many instructions were executed with exactly this call stack
+    count: u64,
+}
+
+/// A subroutine being entered or left, for the timeline of a run
+struct Event {
+    label: Option<usize>,
+    enter: bool,
+    /// How long after the first run started it happened
+    at: Duration,
+}
+
+/// Execution counts and timings gathered while a `Vm` runs a program.
+///
+/// Subroutines are identified by their label, with `None` standing for the
+/// main program. A profile shared by several runs adds them all up.
+pub struct Profile {
+    /// How many times each instruction was executed, by program counter
+    counts: Vec<u64>,
+    subroutines: HashMap<Option<usize>, SubroutineStats>,
+    /// How many times each subroutine called another, by caller and callee
+    calls: HashMap<(Option<usize>, usize), u64>,
+    frames: Vec<OpenFrame>,
+    /// How many frames of each subroutine are open, so recursive calls are
+    /// not counted twice
+    active: HashMap<Option<usize>, usize>,
+    stacks: Vec<StackNode>,
+    /// The index of each call stack in `stacks`, by the index of the call
+    /// stack it extends and the label of the subroutine it adds
+    children: HashMap<(usize, usize), usize>,
+    /// Every subroutine entered and left, in order, if the timeline is
+    /// being recorded
+    events: Option<Vec<Event>>,
+    executed: u64,
+    /// When the innermost subroutine last started or resumed running
+    last: Instant,
+    /// When the first run of the program started
+    started: Instant,
+    elapsed: Duration,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profile {
+    /// Constructs an empty profile
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            counts: vec![],
+            subroutines: HashMap::new(),
+            calls: HashMap::new(),
+            frames: vec![],
+            active: HashMap::new(),
+            stacks: vec![],
+            children: HashMap::new(),
+            events: None,
+            executed: 0,
+            last: now,
+            started: now,
+            elapsed: Duration::default(),
+        }
+    }
+
+    /// Also records when each subroutine is entered and left, so the run
+    /// can be exported with `chrome_trace`. This takes memory for every
+    /// call the program makes
+    pub fn with_timeline(mut self) -> Self {
+        self.events = Some(vec![]);
+        self
+    }
+
+    /// Starts timing a run of the program from its main entry point, unless
+    /// one is already running
+    pub(crate) fn start(&mut self) {
+        if !self.frames.is_empty() {
+            return;
+        }
+
+        let now = Instant::now();
+        if self.stacks.is_empty() {
+            self.started = now;
+        }
+        self.last = now;
+        self.enter(None, now);
+    }
+
+    /// Charges the time since the innermost subroutine started or resumed
+    /// running to it
+    fn switch(&mut self, now: Instant) {
+        let current = self.frames.last().and_then(|x| x.label);
+        self.subroutines.entry(current).or_default().self_time += now - self.last;
+        self.last = now;
+    }
+
+    /// Returns the index of the call stack made by calling `label` with the
+    /// call stack `parent`, adding it if this is the first such call
+    fn stack(&mut self, parent: Option<usize>, label: Option<usize>) -> usize {
+        // Every run of the program starts from the same root
+        if parent.is_none() && !self.stacks.is_empty() {
+            return 0;
+        }
+        let key = match (parent, label) {
+            (Some(parent), Some(label)) => Some((parent, label)),
+            _ => None,
+        };
+        if let Some(idx) = key.and_then(|x| self.children.get(&x)) {
+            return *idx;
+        }
+
+        self.stacks.push(StackNode {
+            parent,
+            label,
+            count: 0,
+        });
+        let idx = self.stacks.len() - 1;
+        if let Some(key) = key {
+            self.children.insert(key, idx);
+        }
+        idx
+    }
+
+    fn enter(&mut self, label: Option<usize>, now: Instant) {
+        self.subroutines.entry(label).or_default().calls += 1;
+        *self.active.entry(label).or_default() += 1;
+        // A subroutine calling itself directly stays in the same call stack,
+        // or deep recursion would make a separate one for every level
+        let parent = self.frames.last().map(|x| x.stack);
+        let stack = match parent {
+            Some(parent) if self.stacks[parent].label == label => parent,
+            _ => self.stack(parent, label),
+        };
+        self.frames.push(OpenFrame {
+            label,
+            executed: self.executed,
+            exclusive: 0,
+            entered: now,
+            stack,
+        });
+
+        if let Some(events) = &mut self.events {
+            events.push(Event {
+                label,
+                enter: true,
+                at: now - self.started,
+            });
+        }
+    }
+
+    fn leave(&mut self, now: Instant) {
+        let frame = match self.frames.pop() {
+            Some(x) => x,
+            None => return,
+        };
+
+        let active = self.active.get_mut(&frame.label).unwrap();
+        *active -= 1;
+        let stats = self.subroutines.get_mut(&frame.label).unwrap();
+        stats.exclusive += frame.exclusive;
+        if *active == 0 {
+            stats.inclusive += self.executed - frame.executed;
+            stats.total_time += now - frame.entered;
+        }
+        self.stacks[frame.stack].count += frame.exclusive;
+        // Leaving main ends the run, whether it returned or was closed
+        // once the program halted
+        if self.frames.is_empty() {
+            self.elapsed += now - frame.entered;
+        }
+
+        if let Some(events) = &mut self.events {
+            events.push(Event {
+                label: frame.label,
+                enter: false,
+                at: now - self.started,
+            });
+        }
+    }
+
+    /// Records that the instruction at `pc` was executed while `depth`
+    /// frames were on the call stack, leaving the call stack as `call_stack`
+    pub(crate) fn record(&mut self, pc: usize, depth: usize, call_stack: &[CallFrame]) {
+        if self.counts.len() <= pc {
+            self.counts.resize(pc + 1, 0);
+        }
+        self.counts[pc] += 1;
+        self.executed += 1;
+
+        if let Some(frame) = self.frames.last_mut() {
+            frame.exclusive += 1;
+        }
+
+        // The clock is only read when the running subroutine changes, which
+        // keeps profiling cheap for the instructions in between
+        if call_stack.len() > depth {
+            let now = Instant::now();
+            self.switch(now);
+            let caller = self.frames.last().and_then(|x| x.label);
+            let callee = call_stack.last().and_then(|x| x.label).unwrap();
+            *self.calls.entry((caller, callee)).or_default() += 1;
+            self.enter(Some(callee), now);
+        } else if call_stack.len() < depth {
+            let now = Instant::now();
+            self.switch(now);
+            self.leave(now);
+        }
+    }
+
+    /// Closes every subroutine still running once the program halts
+    pub(crate) fn finish(&mut self) {
+        if self.frames.is_empty() {
+            return;
+        }
+
+        let now = Instant::now();
+        self.switch(now);
+        while !self.frames.is_empty() {
+            self.leave(now);
+        }
+    }
+
+    /// Returns the total number of instructions executed
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Returns how long the program ran for, over every run
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns how many times the instruction at `pc` was executed
+    pub fn count_at(&self, pc: usize) -> u64 {
+        self.counts.get(pc).cloned().unwrap_or_default()
+    }
+
+    /// Returns how many instructions were executed from each source line
+    pub fn line_counts(&self, program: &Program) -> HashMap<usize, u64> {
+        let mut lines = HashMap::new();
+        for (pc, count) in self.counts.iter().enumerate() {
+            if *count > 0 {
+                *lines.entry(program.line_at(pc)).or_default() += count;
+            }
+        }
+        lines
+    }
+
+    /// Returns the statistics of the subroutine with the given label, or of
+    /// the main program for `None`
+    pub fn subroutine(&self, label: Option<usize>) -> Option<&SubroutineStats> {
+        self.subroutines.get(&label)
+    }
+
+    /// Returns how many times `caller` called the subroutine `callee`
+    pub fn call_count(&self, caller: Option<usize>, callee: usize) -> u64 {
+        self.calls
+            .get(&(caller, callee))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Formats the profile as a report of the hottest lines, instructions,
+    /// subroutines and calls, most expensive first
+    pub fn report(&self, program: &Program) -> String {
+        let name = |label| frame_name(program, label);
+        let percent = |count: u64| 100.0 * count as f64 / self.executed.max(1) as f64;
+        let millis = |x: Duration| x.as_secs_f64() * 1000.0;
+
+        let mut out = format!(
+            "Executed {} instructions in {:.3} ms\n",
+            self.executed,
+            millis(self.elapsed)
+        );
+
+        let mut lines: Vec<_> = self.line_counts(program).into_iter().collect();
+        lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        out.push_str("\nHottest lines:\n       count       %   line\n");
+        for (line, count) in lines.into_iter().take(REPORT_ROWS) {
+            writeln!(out, "{:>12} {:>6.2}% {:>6}", count, percent(count), line).unwrap();
+        }
+
+        let mut pcs: Vec<_> = (0..self.counts.len())
+            .filter(|x| self.counts[*x] > 0)
+            .collect();
+        pcs.sort_by(|a, b| self.counts[*b].cmp(&self.counts[*a]).then(a.cmp(b)));
+        out.push_str("\nHottest instructions:\n       count       %     pc   line  instruction\n");
+        for pc in pcs.into_iter().take(REPORT_ROWS) {
+            let count = self.counts[pc];
+            writeln!(
+                out,
+                "{:>12} {:>6.2}%   {:04} {:>6}  {}",
+                count,
+                percent(count),
+                pc,
+                program.line_at(pc),
+                mnemonic(program, program.inst_at(pc))
+            )
+            .unwrap();
+        }
+
+        let mut subroutines: Vec<_> = self.subroutines.iter().collect();
+        subroutines.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+        out.push_str(
+            "\nSubroutines:\n       calls    inclusive    exclusive    total ms     self ms  name\n",
+        );
+        for (label, stats) in subroutines {
+            writeln!(
+                out,
+                "{:>12} {:>12} {:>12} {:>11.3} {:>11.3}  {}",
+                stats.calls,
+                stats.inclusive,
+                stats.exclusive,
+                millis(stats.total_time),
+                millis(stats.self_time),
+                name(*label)
+            )
+            .unwrap();
+        }
+
+        let mut calls: Vec<_> = self.calls.iter().collect();
+        calls.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        out.push_str("\nCalls:\n       count  caller -> callee\n");
+        for ((caller, callee), count) in calls {
+            writeln!(
+                out,
+                "{:>12}  {} -> {}",
+                count,
+                name(*caller),
+                name(Some(*callee))
+            )
+            .unwrap();
+        }
+
+        out.pop();
+        out
+    }
+
+    /// Formats the instructions executed with each distinct call stack in
+    /// the folded format read by flame graph tools: one line per call
+    /// stack, with its frames from the outermost to the innermost joined by
+    /// `;`, followed by a space and the instruction count. Directly
+    /// recursive calls share a single frame
+    pub fn folded(&self, program: &Program) -> String {
+        let mut lines = vec![];
+        for (idx, node) in self.stacks.iter().enumerate() {
+            if node.count == 0 {
+                continue;
+            }
+
+            let mut frames = vec![];
+            let mut next = Some(idx);
+            while let Some(idx) = next {
+                frames.push(frame_name(program, self.stacks[idx].label));
+                next = self.stacks[idx].parent;
+            }
+            frames.reverse();
+            lines.push(format!("{} {}", frames.join(";"), node.count));
+        }
+
+        lines.sort();
+        lines.join("\n")
+    }
+
+    /// Formats the timeline of the run in the Chrome trace event format,
+    /// with a begin and an end event each time a subroutine is entered and
+    /// left. Returns `None` unless the profile was made `with_timeline`
+    pub fn chrome_trace(&self, program: &Program) -> Option<String> {
+        let events = self.events.as_ref()?.iter().map(|event| {
+            let micros = event.at.as_secs_f64() * 1_000_000.0;
+            Object::new()
+                .string("name", &frame_name(program, event.label))
+                .string("cat", "subroutine")
+                .string("ph", if event.enter { "B" } else { "E" })
+                .raw("ts", &format!("{:.3}", micros))
+                .number("pid", 1)
+                .number("tid", 1)
+                .finish()
+        });
+
+        let trace = Object::new()
+            .raw("traceEvents", &json::array(events))
+            .string("displayTimeUnit", "ms")
+            .finish();
+        Some(trace)
+    }
+}
+
+/// Returns the name a subroutine is shown with in exported profiles
+fn frame_name(program: &Program, label: Option<usize>) -> String {
+    match label {
+        Some(label) => label_name(program, label),
+        None => "main".to_string(),
+    }
+}
@@ -0,0 +1,195 @@
+use crate::json::{self, Object};
+use crate::value::Value;
+use crate::vm::CallFrame;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+/// How many values from the top of the stack each trace record shows
+const TRACE_VALUES: usize = 8;
+
+/// How trace records are written
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum TraceFormat {
+    /// One aligned line of text per instruction
+    #[default]
+    Human,
+    /// One JSON object per line
+    Json,
+}
+
+/// Which executed instructions are traced. An instruction is traced if it
+/// passes every filter that is set
+#[derive(Clone, Debug, Default)]
+pub struct TraceFilter {
+    /// The source lines instructions must come from, or `None` for any line
+    pub lines: Option<RangeInclusive<usize>>,
+    /// The subroutine labels instructions must run under, either directly
+    /// or in something they call, or empty for any subroutine
+    pub labels: Vec<usize>,
+}
+
+impl TraceFilter {
+    /// Returns true if the instruction on `line` run with the given call
+    /// stack is traced
+    fn matches(&self, line: usize, call_stack: &[CallFrame]) -> bool {
+        let in_lines = self.lines.as_ref().is_none_or(|x| x.contains(&line));
+        let in_labels = self.labels.is_empty()
+            || call_stack
+                .iter()
+                .any(|frame| frame.label.is_some_and(|x| self.labels.contains(&x)));
+        in_lines && in_labels
+    }
+}
+
+/// Something an instruction did besides changing the stack
+pub(crate) enum Effect {
+    /// A value was written to a heap address
+    Store(Value, Value),
+    /// Text was written to the program's output
+    Output(String),
+    /// Text was read from the program's input
+    Input(String),
+}
+
+impl Effect {
+    fn to_json(&self) -> String {
+        match self {
+            Effect::Store(addr, value) => Object::new()
+                .string("type", "store")
+                .number("address", addr)
+                .number("value", value)
+                .finish(),
+            Effect::Output(text) => Object::new()
+                .string("type", "output")
+                .string("text", text)
+                .finish(),
+            Effect::Input(text) => Object::new()
+                .string("type", "input")
+                .string("text", text)
+                .finish(),
+        }
+    }
+}
+
+/// A single executed instruction
+pub(crate) struct Record {
+    /// How many instructions were executed before this one
+    pub step: u64,
+    pub pc: usize,
+    pub line: usize,
+    pub column: usize,
+    pub instruction: String,
+    /// The label of the subroutine the instruction ran in, or `None` for
+    /// main
+    pub label: Option<usize>,
+    /// The values at the top of the stack before the instruction, with the
+    /// top value last, and the total number of values
+    pub before: (Vec<Value>, usize),
+    pub after: (Vec<Value>, usize),
+    pub effects: Vec<Effect>,
+    /// The name of the runtime error the instruction failed with, if any
+    pub error: Option<&'static str>,
+}
+
+/// Returns the top of the stack as a `Record` stores it
+pub(crate) fn stack_top(stack: &[Value]) -> (Vec<Value>, usize) {
+    let skip = stack.len().saturating_sub(TRACE_VALUES);
+    (stack[skip..].to_vec(), stack.len())
+}
+
+/// Writes the top of a stack as `[... 1 2 3]`
+fn write_stack(out: &mut String, (values, depth): &(Vec<Value>, usize)) {
+    out.push('[');
+    if *depth > values.len() {
+        out.push_str("...");
+        if !values.is_empty() {
+            out.push(' ');
+        }
+    }
+    let values: Vec<_> = values.iter().map(Value::to_string).collect();
+    out.push_str(&values.join(" "));
+    out.push(']');
+}
+
+/// Logs every instruction a `Vm` executes that passes its filter
+pub struct Tracer<'a> {
+    output: Box<dyn Write + 'a>,
+    format: TraceFormat,
+    filter: TraceFilter,
+}
+
+impl<'a> Tracer<'a> {
+    /// Constructs a tracer writing every executed instruction to `output`
+    pub fn new<W: Write + 'a>(output: W, format: TraceFormat) -> Self {
+        Self {
+            output: Box::new(output),
+            format,
+            filter: TraceFilter::default(),
+        }
+    }
+
+    /// Sets which instructions are traced
+    pub fn with_filter(mut self, filter: TraceFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub(crate) fn traces(&self, line: usize, call_stack: &[CallFrame]) -> bool {
+        self.filter.matches(line, call_stack)
+    }
+
+    pub(crate) fn write(&mut self, record: &Record) -> io::Result<()> {
+        match self.format {
+            TraceFormat::Human => writeln!(self.output, "{}", human(record)),
+            TraceFormat::Json => writeln!(self.output, "{}", to_json(record)),
+        }
+    }
+}
+
+fn human(record: &Record) -> String {
+    let mut out = format!(
+        "{:>8}  {:04}  {:>5}:{:<4} {:<20} ",
+        record.step, record.pc, record.line, record.column, record.instruction
+    );
+    write_stack(&mut out, &record.before);
+    out.push_str(" -> ");
+    write_stack(&mut out, &record.after);
+
+    for effect in &record.effects {
+        match effect {
+            Effect::Store(addr, value) => write!(out, "  heap[{}] = {}", addr, value),
+            Effect::Output(text) => write!(out, "  output {:?}", text),
+            Effect::Input(text) => write!(out, "  input {:?}", text),
+        }
+        .unwrap();
+    }
+    if let Some(error) = record.error {
+        write!(out, "  error {}", error).unwrap();
+    }
+    out
+}
+
+fn to_json(record: &Record) -> String {
+    let stack =
+        |(values, _): &(Vec<Value>, usize)| json::array(values.iter().map(Value::to_string));
+    let error = record.error.map_or("null".to_string(), json::string);
+
+    Object::new()
+        .number("step", record.step)
+        .number("pc", record.pc)
+        .number("line", record.line)
+        .number("column", record.column)
+        .string("instruction", &record.instruction)
+        .optional("label", record.label)
+        .raw("stack_before", &stack(&record.before))
+        .number("depth_before", record.before.1)
+        .raw("stack_after", &stack(&record.after))
+        .number("depth_after", record.after.1)
+        .raw(
+            "effects",
+            &json::array(record.effects.iter().map(Effect::to_json)),
+        )
+        .raw("error", &error)
+        .finish()
+}
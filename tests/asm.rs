@@ -0,0 +1,139 @@
+//! Checks the assembler, and that disassembling a program and assembling it
+//! again gives back the same program.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use whitespace_vm::asm::{assemble, disassemble, to_whitespace, AsmErrorKind};
+use whitespace_vm::{Instruction, Parser, Program, Vm};
+
+/// Lists every instruction of `program` with the constant each `Push`
+/// refers to, so programs with differently ordered constant pools compare
+/// equal
+fn instructions(program: &Program) -> Vec<String> {
+    program
+        .instructions()
+        .iter()
+        .map(|inst| match inst {
+            Instruction::Push(idx) => format!("Push({})", program.get_const(*idx)),
+            inst => format!("{:?}", inst),
+        })
+        .collect()
+}
+
+/// Returns the error kind, line and column `source` fails to assemble with
+fn asm_error(source: &str) -> (AsmErrorKind, usize, usize) {
+    match assemble(source) {
+        Ok(_) => panic!("{:?} assembled", source),
+        Err(error) => (error.kind().clone(), error.line_no(), error.column()),
+    }
+}
+
+#[test]
+fn examples_round_trip() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|x| x != "ws") {
+            continue;
+        }
+        count += 1;
+
+        let source = fs::read_to_string(&path).unwrap();
+        let program = Parser::new(&source).parse().unwrap();
+        let text = disassemble(&program);
+        let assembled = assemble(&text).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert_eq!(
+            instructions(&assembled),
+            instructions(&program),
+            "{}",
+            path.display()
+        );
+
+        let whitespace = to_whitespace(&assembled);
+        let parsed = Parser::new(&whitespace).parse().unwrap();
+        assert_eq!(
+            instructions(&parsed),
+            instructions(&program),
+            "{}",
+            path.display()
+        );
+    }
+    assert_eq!(count, 9);
+}
+
+#[test]
+fn labels_and_char_literals() {
+    let program = assemble(
+        "\
+; prints hi
+        push 'h'
+        call print
+        push 'i'   # same as push 105
+        call print
+        push '\\n'
+        call print
+        end
+print:  outc
+        ret
+",
+    )
+    .unwrap();
+
+    let mut output = vec![];
+    Vm::with_io(&program, Cursor::new(""), &mut output)
+        .run()
+        .unwrap();
+    assert_eq!(output, b"hi\n");
+    assert_eq!(program.find_label("print"), Some(0));
+    assert_eq!(program.label_pc(0), Some(7));
+    assert!(disassemble(&program).contains("; subroutine print\nprint:\n    outc"));
+}
+
+#[test]
+fn escaped_char_literals() {
+    let program = assemble("push '\\''\npush '\\\\'\npush ';'\npush '\\0'\nend").unwrap();
+    let pushed = instructions(&program);
+    assert_eq!(pushed[..4], ["Push(39)", "Push(92)", "Push(59)", "Push(0)"]);
+}
+
+#[test]
+fn errors_point_at_the_offending_word() {
+    assert_eq!(
+        asm_error("push 1\n  frob 2"),
+        (AsmErrorKind::UnknownMnemonic("frob".to_string()), 2, 3)
+    );
+    assert_eq!(asm_error("push"), (AsmErrorKind::MissingOperand, 1, 5));
+    assert_eq!(asm_error("dup 1"), (AsmErrorKind::UnexpectedOperand, 1, 5));
+    assert_eq!(
+        asm_error("push 1 2"),
+        (AsmErrorKind::UnexpectedOperand, 1, 8)
+    );
+    assert_eq!(asm_error("push x1"), (AsmErrorKind::InvalidNumber, 1, 6));
+    assert_eq!(asm_error("copy -1"), (AsmErrorKind::NegativeOperand, 1, 6));
+    assert_eq!(
+        asm_error("jmp 1abc"),
+        (AsmErrorKind::InvalidLabelName, 1, 5)
+    );
+    assert_eq!(
+        asm_error("1abc: end"),
+        (AsmErrorKind::InvalidLabelName, 1, 1)
+    );
+    assert_eq!(
+        asm_error("push 'ab'"),
+        (AsmErrorKind::InvalidCharLiteral, 1, 6)
+    );
+    assert_eq!(
+        asm_error("push 'a"),
+        (AsmErrorKind::InvalidCharLiteral, 1, 6)
+    );
+    assert_eq!(
+        asm_error("end\n\njz nowhere"),
+        (AsmErrorKind::UndefinedLabel("nowhere".to_string()), 3, 4)
+    );
+    assert_eq!(
+        asm_error("a: dup\na: end"),
+        (AsmErrorKind::DuplicateLabel("a".to_string()), 2, 1)
+    );
+}
@@ -0,0 +1,136 @@
+//! Runs the `wspace` binary to check what its commands print and how they
+//! exit.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs `wspace` with `args`, feeding it `stdin`
+fn wspace(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wspace"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn no_fuel_runs_nothing() {
+    // Prints 1, then ends
+    let output = wspace(&["--fuel=0", "-"], "   \t\n\t\n \t\n\n\n");
+    assert_eq!(output.status.code(), Some(16));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("budget was exhausted"), "{}", stderr);
+}
+
+/// Runs `wspace` with `args` and `--error-format=json`, returning its exit
+/// code and the lines it wrote to stderr
+fn json_errors(args: &[&str], stdin: &str) -> (Option<i32>, Vec<String>) {
+    let args: Vec<_> = args
+        .iter()
+        .copied()
+        .chain(["--error-format=json"])
+        .collect();
+    let output = wspace(&args, stdin);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    (
+        output.status.code(),
+        stderr.lines().map(String::from).collect(),
+    )
+}
+
+#[test]
+fn json_parse_errors_and_warnings() {
+    // Pushes 1, then stops partway through the next instruction
+    let (code, lines) = json_errors(&["check", "-"], "  \t\n\n");
+    assert_eq!(code, Some(4));
+    assert_eq!(
+        lines,
+        [
+            r#"{"type":"parse_error","kind":"unexpected_eof","message":"Unexpected end of file.","line":2,"column":1,"span":{"start":{"offset":4,"line":2,"column":1},"end":{"offset":5,"line":3,"column":1}}}"#
+        ]
+    );
+
+    // Marks label 1, then ends
+    let (code, lines) = json_errors(&["check", "-"], "\n  \t\n\n\n\n");
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        lines,
+        [
+            r#"{"type":"warning","kind":"unused_label","label":1,"message":"Label #1 is never used.","line":1,"column":1,"span":{"start":{"offset":0,"line":1,"column":1},"end":{"offset":5,"line":3,"column":1}}}"#
+        ]
+    );
+}
+
+#[test]
+fn json_runtime_error() {
+    // push 1, push 99, call f, end, f: retrieve, ret
+    let source = "   \t\n   \t\t   \t\t\n\n \t \n\n\n\n\n   \n\t\t\t\n\t\n";
+    let (code, lines) = json_errors(&["-"], source);
+    assert_eq!(code, Some(11));
+    assert_eq!(lines.len(), 1);
+    assert!(
+        lines[0].starts_with(
+            r#"{"type":"runtime_error","reason":"invalid_heap_entry","address":99,"message":"Attempted to access invalid heap entry 99","pc":4,"instruction":"retrieve","stack_top":[1,99],"stack_depth":2,"frames":[{"line":3,"column":1,"label":null,"#
+        ),
+        "{}",
+        lines[0]
+    );
+    assert!(lines[0].contains(r#""label":0,"#), "{}", lines[0]);
+}
+
+#[test]
+fn json_asm_errors() {
+    let (code, lines) = json_errors(&["asm", "-"], "push 1\n  frob 2\n");
+    assert_eq!(code, Some(4));
+    assert_eq!(
+        lines,
+        [
+            r#"{"type":"asm_error","kind":"unknown_mnemonic","mnemonic":"frob","message":"Unknown instruction 'frob'.","line":2,"column":3}"#
+        ]
+    );
+
+    let (code, lines) = json_errors(&["asm", "-"], "jz nowhere\n");
+    assert_eq!(code, Some(4));
+    assert_eq!(
+        lines,
+        [
+            r#"{"type":"asm_error","kind":"undefined_label","label":"nowhere","message":"Undefined label 'nowhere'.","line":1,"column":4}"#
+        ]
+    );
+}
+
+#[test]
+fn json_file_errors() {
+    for command in ["run", "check", "asm"] {
+        let (code, lines) = json_errors(&[command, "no/such/file.ws"], "");
+        assert_eq!(code, Some(3));
+        assert_eq!(
+            lines,
+            [
+                r#"{"type":"io_error","message":"Could not open 'no/such/file.ws'","path":"no/such/file.ws"}"#
+            ]
+        );
+    }
+}
+
+#[test]
+fn only_check_prints_warnings_by_default() {
+    // Marks label 1, then prints 1 and ends
+    let source = format!("\n  \t\n{}", "   \t\n\t\n \t\n\n\n");
+    let stderr = |args: &[&str]| String::from_utf8(wspace(args, &source).stderr).unwrap();
+
+    assert!(stderr(&["check", "-"]).contains("Label #1 is never used."));
+    assert_eq!(stderr(&["check", "--no-warnings", "-"]), "");
+    assert_eq!(stderr(&["run", "-"]), "");
+    assert!(stderr(&["run", "--warnings", "-"]).contains("Label #1 is never used."));
+}
@@ -0,0 +1,116 @@
+//! Runs every bundled example with its checked-in input and compares what it
+//! prints with the checked-in expected output.
+//!
+//! Each `examples/NAME.ws` has an `examples/NAME.out` with its expected
+//! output, and an `examples/NAME.in` if it reads any input.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use whitespace_vm::Vm;
+
+fn example_path(name: &str, extension: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("examples");
+    path.push(name);
+    path.set_extension(extension);
+    path
+}
+
+/// Describes the first line where `actual` and `expected` differ
+fn first_difference(actual: &str, expected: &str) -> String {
+    let mut actual_lines = actual.split('\n');
+    let mut expected_lines = expected.split('\n');
+
+    for line_no in 1.. {
+        match (actual_lines.next(), expected_lines.next()) {
+            (Some(a), Some(e)) if a == e => {}
+            (a, e) => {
+                return format!(
+                    "first difference on line {}:\n  expected: {:?}\n  actual:   {:?}",
+                    line_no, e, a
+                );
+            }
+        }
+    }
+    unreachable!()
+}
+
+fn run_example(name: &str) {
+    let source = fs::read_to_string(example_path(name, "ws")).unwrap();
+    let input = fs::read(example_path(name, "in")).unwrap_or_default();
+    let expected = fs::read_to_string(example_path(name, "out")).unwrap();
+
+    let program = whitespace_vm::parse(&source).unwrap();
+    let mut output = vec![];
+    let result = Vm::with_io(&program, Cursor::new(input), &mut output).run();
+    if let Err(traceback) = result {
+        panic!("{} failed:\n{}", name, traceback);
+    }
+
+    let actual = String::from_utf8(output).unwrap();
+    if actual != expected {
+        panic!(
+            "output of {} differs, {}",
+            name,
+            first_difference(&actual, &expected)
+        );
+    }
+}
+
+#[test]
+fn every_example_has_expected_output() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|x| x == "ws") {
+            let expected = path.with_extension("out");
+            assert!(expected.exists(), "{} is missing", expected.display());
+        }
+    }
+}
+
+#[test]
+fn calc() {
+    run_example("calc");
+}
+
+#[test]
+fn count() {
+    run_example("count");
+}
+
+#[test]
+fn fact() {
+    run_example("fact");
+}
+
+#[test]
+fn fibonacci() {
+    run_example("fibonacci");
+}
+
+#[test]
+fn hanoi() {
+    run_example("hanoi");
+}
+
+#[test]
+fn hworld() {
+    run_example("hworld");
+}
+
+#[test]
+fn loctest() {
+    run_example("loctest");
+}
+
+#[test]
+fn name() {
+    run_example("name");
+}
+
+#[test]
+fn sudoku() {
+    run_example("sudoku");
+}
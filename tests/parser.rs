@@ -0,0 +1,133 @@
+//! Checks that the parser recovers from errors without reporting errors
+//! that are not there, and that the partial program it recovers is safe to
+//! use.
+
+use std::io::Cursor;
+use whitespace_vm::asm::{self, disassemble};
+use whitespace_vm::{ErrorKind, Parser, RuntimeError, Vm};
+
+/// Prints 1 and ends, written as `push 1; outn; end`
+const PRINT_ONE: &str = "   \t\n\t\n \t\n\n\n";
+
+#[test]
+fn duplicate_label_is_the_only_error() {
+    let mark = "\n   \n";
+    let source = format!("{}{}{}", mark, mark, PRINT_ONE);
+    let output = Parser::new(&source).parse_all();
+
+    let kinds: Vec<_> = output.errors.iter().map(|x| x.kind()).collect();
+    assert_eq!(kinds, [ErrorKind::DuplicateLabel(1)]);
+    assert_eq!(output.program.inst_count(), 3);
+}
+
+#[cfg(not(feature = "bignum"))]
+#[test]
+fn oversized_literal_is_the_only_error() {
+    let push = format!("   {}\n", "\t".repeat(70));
+    let source = format!("{}{}", push, PRINT_ONE);
+    let output = Parser::new(&source).parse_all();
+
+    let kinds: Vec<_> = output.errors.iter().map(|x| x.kind()).collect();
+    assert_eq!(kinds, [ErrorKind::LiteralOverflow]);
+    assert_eq!(output.program.inst_count(), 3);
+}
+
+#[test]
+fn unresolved_jump_in_partial_program() {
+    // jmp 1, end, where label 1 is never marked
+    let output = Parser::new("\n \n\t\n\n\n\n").parse_all();
+    let kinds: Vec<_> = output.errors.iter().map(|x| x.kind()).collect();
+    assert_eq!(kinds, [ErrorKind::InvalidLabel]);
+
+    let text = disassemble(&output.program);
+    assert!(text.contains("jmp label_0"), "{}", text);
+    let source = asm::to_whitespace(&output.program);
+    assert!(source.starts_with("\n \n \n"), "{:?}", source);
+}
+
+#[test]
+fn unresolved_call_in_partial_program() {
+    // call 3, end, where label 3 is never marked
+    let output = Parser::new("\n \t\t\t\n\n\n\n").parse_all();
+    assert_eq!(output.errors.len(), 1);
+    assert!(disassemble(&output.program).contains("call label_0"));
+
+    let result = Vm::with_io(&output.program, Cursor::new(""), vec![]).run();
+    match result {
+        Err(traceback) => assert_eq!(traceback.reason, RuntimeError::UnresolvedCall),
+        Ok(_) => panic!("the call succeeded"),
+    }
+}
+
+#[test]
+fn jump_past_the_end_is_an_error() {
+    // jmp 0, end, then label 0 is marked after the last instruction
+    let output = Parser::new("\n \n \n\n\n\n\n   \n").parse_all();
+    let kinds: Vec<_> = output.errors.iter().map(|x| x.kind()).collect();
+    assert_eq!(kinds, [ErrorKind::JumpPastEnd]);
+    assert_eq!(output.errors[0].line_no(), 1);
+}
+
+#[test]
+fn errors_render_the_valid_sequences() {
+    // An arithmetic prefix followed by a newline
+    let source = "\t \n\n";
+    let output = Parser::new(source).parse_all();
+    assert_eq!(
+        output.errors[0].render(source),
+        "\
+[Line 1:1] Invalid arithmetic instruction.
+  |
+1 | →·↵
+  | ^^^
+2 | ↵
+  | ^
+  = expected one of:
+        →···  add
+        →··→  sub
+        →··↵  mul
+        →·→·  div
+        →·→→  mod
+  = note: · is a space, → is a tab and ↵ is a newline"
+    );
+
+    // A tab followed by the end of the source
+    let output = Parser::new("\t").parse_all();
+    let rendered = output.errors[0].render("\t");
+    assert!(
+        rendered.contains("→·  arithmetic\n        →→  heap access\n        →↵  I/O\n"),
+        "{}",
+        rendered
+    );
+}
+
+#[test]
+fn oversized_operand_message() {
+    // copy with a 70 bit operand
+    let source = format!(" \t {}\n", "\t".repeat(70));
+    let output = Parser::new(&source).parse_all();
+    let message = output.errors[0].to_string();
+    if cfg!(feature = "bignum") {
+        assert!(
+            message.ends_with("Copy or slide operand too large."),
+            "{}",
+            message
+        );
+    } else {
+        assert!(
+            message.ends_with("Literal too large to fit in an i64."),
+            "{}",
+            message
+        );
+    }
+}
+
+#[test]
+fn no_warnings_alongside_errors() {
+    // Marks label 1, then jumps to it, but the source ends before the label
+    // does
+    let output = Parser::new("\n  \t\n\n \n\t").parse_all();
+    let kinds: Vec<_> = output.errors.iter().map(|x| x.kind()).collect();
+    assert_eq!(kinds, [ErrorKind::UnexpectedEof]);
+    assert!(output.warnings.is_empty(), "{:?}", output.warnings);
+}
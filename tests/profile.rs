@@ -0,0 +1,155 @@
+//! Checks the counts a `Profile` gathers while a program runs.
+
+use std::io::Cursor;
+use whitespace_vm::asm::assemble;
+use whitespace_vm::{Profile, Program, Vm};
+
+/// Calls `countdown` with 3, which calls itself until it reaches zero and
+/// calls `leaf` each time
+const PROGRAM: &str = "\
+    push 3
+    call countdown
+    pop
+    end
+countdown:
+    call leaf
+    dup
+    jz done
+    push 1
+    sub
+    call countdown
+done:
+    ret
+leaf:
+    ret
+";
+
+/// Runs `PROGRAM` with `profile` gathering counts, returning the program
+/// along with the profile
+fn profile(mut profile: Profile) -> (Program, Profile) {
+    let program = assemble(PROGRAM).unwrap();
+    Vm::with_io(&program, Cursor::new(""), vec![])
+        .with_profile(&mut profile)
+        .run()
+        .unwrap();
+    (program, profile)
+}
+
+#[test]
+fn counts_instructions_and_calls() {
+    let (program, profile) = profile(Profile::new());
+    let countdown = program.find_label("countdown").unwrap();
+    let leaf = program.find_label("leaf").unwrap();
+
+    // main runs 4 instructions, each of the 4 calls to countdown runs 4,
+    // plus 3 more in all but the last, and leaf runs 1 each time
+    assert_eq!(profile.executed(), 4 + 4 * 4 + 3 * 3 + 4);
+    assert_eq!(profile.count_at(0), 1);
+    assert_eq!(profile.count_at(4), 4);
+
+    let main = profile.subroutine(None).unwrap();
+    assert_eq!(main.calls, 1);
+    assert_eq!(main.exclusive, 4);
+    assert_eq!(main.inclusive, profile.executed());
+
+    let stats = profile.subroutine(Some(countdown)).unwrap();
+    assert_eq!(stats.calls, 4);
+    assert_eq!(stats.exclusive, 4 * 4 + 3 * 3);
+    assert_eq!(stats.inclusive, 4 * 4 + 3 * 3 + 4);
+
+    let stats = profile.subroutine(Some(leaf)).unwrap();
+    assert_eq!((stats.calls, stats.exclusive, stats.inclusive), (4, 4, 4));
+
+    assert_eq!(profile.call_count(None, countdown), 1);
+    assert_eq!(profile.call_count(Some(countdown), countdown), 3);
+    assert_eq!(profile.call_count(Some(countdown), leaf), 4);
+    assert_eq!(profile.call_count(None, leaf), 0);
+
+    let lines = profile.line_counts(&program);
+    assert_eq!(lines[&1], 1);
+    assert_eq!(lines[&6], 4);
+}
+
+#[test]
+fn report_lists_hotspots() {
+    let (program, profile) = profile(Profile::new());
+
+    let report = profile.report(&program);
+    assert!(report.starts_with("Executed 33 instructions"));
+    let calls = report.split("\nCalls:\n").nth(1).unwrap();
+    let edges: Vec<_> = calls.lines().skip(1).map(str::trim).collect();
+    assert_eq!(
+        edges,
+        [
+            "4  countdown -> leaf",
+            "3  countdown -> countdown",
+            "1  main -> countdown"
+        ]
+    );
+}
+
+#[test]
+fn folded_stacks() {
+    let (program, profile) = profile(Profile::new());
+
+    assert_eq!(
+        profile.folded(&program),
+        "\
+main 4
+main;countdown 25
+main;countdown;leaf 4"
+    );
+    assert!(profile.chrome_trace(&program).is_none());
+}
+
+#[test]
+fn chrome_trace() {
+    let (program, profile) = profile(Profile::new().with_timeline());
+
+    let trace = profile.chrome_trace(&program).unwrap();
+    assert!(trace
+        .starts_with("{\"traceEvents\":[{\"name\":\"main\",\"cat\":\"subroutine\",\"ph\":\"B\""));
+    // main and the 8 calls are each entered and left once
+    assert_eq!(trace.matches("\"ph\":\"B\"").count(), 9);
+    assert_eq!(trace.matches("\"ph\":\"E\"").count(), 9);
+}
+
+#[test]
+fn profile_spans_runs() {
+    let program = assemble(PROGRAM).unwrap();
+    let mut profile = Profile::new();
+    // A VM that never runs leaves no trace in the profile
+    drop(Vm::with_io(&program, Cursor::new(""), vec![]).with_profile(&mut profile));
+    for _ in 0..2 {
+        Vm::with_io(&program, Cursor::new(""), vec![])
+            .with_profile(&mut profile)
+            .run()
+            .unwrap();
+    }
+
+    assert_eq!(profile.executed(), 2 * 33);
+    let main = profile.subroutine(None).unwrap();
+    assert_eq!((main.calls, main.exclusive, main.inclusive), (2, 8, 66));
+    assert_eq!(
+        profile.folded(&program),
+        "\
+main 8
+main;countdown 50
+main;countdown;leaf 8"
+    );
+}
+
+#[test]
+fn main_returning_ends_the_run() {
+    let program = assemble("push 1\npop\nret").unwrap();
+    let mut profile = Profile::new();
+    Vm::with_io(&program, Cursor::new(""), vec![])
+        .with_profile(&mut profile)
+        .run()
+        .unwrap();
+
+    let main = profile.subroutine(None).unwrap();
+    assert_eq!((main.calls, main.inclusive), (1, 3));
+    // The run lasted exactly as long as main did
+    assert_eq!(profile.elapsed(), main.total_time);
+}
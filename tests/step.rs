@@ -0,0 +1,133 @@
+//! Checks stepping through a program, and pausing it while it waits for
+//! input that is not ready yet.
+
+use std::cell::RefCell;
+use std::io::{self, BufRead, Cursor, Read};
+use std::rc::Rc;
+use whitespace_vm::asm::assemble;
+use whitespace_vm::{RuntimeError, Status, Value, Vm};
+
+/// Input handed to the VM a piece at a time. Reading while nothing has been
+/// handed over reports `WouldBlock`, until the feed is closed
+#[derive(Default)]
+struct Feed {
+    /// What was handed over but not yet taken by the reader, and whether
+    /// the feed is closed
+    shared: Rc<RefCell<(Vec<u8>, bool)>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Feed {
+    /// Returns a handle the test hands input over with
+    fn handle(&self) -> Rc<RefCell<(Vec<u8>, bool)>> {
+        Rc::clone(&self.shared)
+    }
+}
+
+impl Read for Feed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for Feed {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.buf.len() {
+            let mut shared = self.shared.borrow_mut();
+            self.buf = std::mem::take(&mut shared.0);
+            self.pos = 0;
+            if self.buf.is_empty() && !shared.1 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
+
+/// Returns the value stored at heap address 0
+fn heap_0(vm: &Vm) -> Option<String> {
+    vm.heap().get(&Value::from(0)).map(ToString::to_string)
+}
+
+#[test]
+fn read_char_waits_for_input() {
+    let program = assemble("push 0\nreadc\nend").unwrap();
+    let feed = Feed::default();
+    let input = feed.handle();
+    let mut vm = Vm::with_io(&program, feed, vec![]);
+
+    assert!(matches!(vm.step(), Status::Continue));
+    for _ in 0..3 {
+        assert!(matches!(vm.step(), Status::NeedsInput));
+        // The instruction is retried rather than counted
+        assert_eq!((vm.pc(), vm.executed()), (1, 1));
+    }
+
+    input.borrow_mut().0.extend(b"A");
+    assert!(matches!(vm.step(), Status::Continue));
+    assert_eq!(heap_0(&vm), Some("65".to_string()));
+    assert!(matches!(vm.step(), Status::Halted));
+    assert!(matches!(vm.step(), Status::Halted));
+}
+
+#[test]
+fn read_num_keeps_a_partial_line() {
+    let program = assemble("push 0\nreadn\nend").unwrap();
+    let feed = Feed::default();
+    let input = feed.handle();
+    let mut vm = Vm::with_io(&program, feed, vec![]);
+
+    input.borrow_mut().0.extend(b"-1");
+    assert!(matches!(vm.run_for(10), Status::NeedsInput));
+    input.borrow_mut().0.extend(b"2");
+    assert!(matches!(vm.step(), Status::NeedsInput));
+    assert_eq!(heap_0(&vm), None);
+
+    input.borrow_mut().0.extend(b"3\n");
+    assert!(matches!(vm.step(), Status::Continue));
+    assert_eq!(heap_0(&vm), Some("-123".to_string()));
+}
+
+#[test]
+fn read_num_takes_a_partial_line_at_the_end_of_input() {
+    let program = assemble("push 0\nreadn\nend").unwrap();
+    let feed = Feed::default();
+    let input = feed.handle();
+    let mut vm = Vm::with_io(&program, feed, vec![]);
+
+    input.borrow_mut().0.extend(b"42");
+    assert!(matches!(vm.run_for(10), Status::NeedsInput));
+    input.borrow_mut().1 = true;
+    assert!(matches!(vm.run_for(10), Status::Halted));
+    assert_eq!(heap_0(&vm), Some("42".to_string()));
+}
+
+#[test]
+fn run_for_stops_after_count_instructions() {
+    let program = assemble("push 1\npush 2\nadd\nend").unwrap();
+    let mut vm = Vm::with_io(&program, Cursor::new(""), vec![]);
+
+    assert!(matches!(vm.run_for(2), Status::Continue));
+    assert_eq!((vm.pc(), vm.executed()), (2, 2));
+    assert!(matches!(vm.run_for(10), Status::Halted));
+    assert_eq!(vm.executed(), 4);
+    assert!(vm.is_halted());
+}
+
+#[test]
+fn run_treats_pending_input_as_an_error() {
+    let program = assemble("push 0\nreadc\nend").unwrap();
+    let traceback = Vm::with_io(&program, Feed::default(), vec![])
+        .run()
+        .unwrap_err();
+    assert_eq!((traceback.reason, traceback.pc), (RuntimeError::IoError, 1));
+}
@@ -0,0 +1,146 @@
+//! Checks the fixture based test runner behind `wspace test`.
+
+use std::fs;
+use std::path::PathBuf;
+use whitespace_vm::testing::{self, Failure};
+use whitespace_vm::Config;
+
+/// Pushes 1, pushes 0, then divides them
+const DIVIDE_BY_ZERO: &str = "   \t\n   \n\t \t \n\n\n";
+
+/// Loops forever
+const INFINITE_LOOP: &str = "\n   \n\n \n\n";
+
+/// Creates an empty directory for a test's fixtures
+fn fixture_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn examples_pass() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let cases = testing::discover(dir).unwrap();
+    assert_eq!(cases.len(), 9);
+
+    let results = testing::run_all(&cases, &Config::default(), 4);
+    let names: Vec<_> = results.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "calc",
+            "count",
+            "fact",
+            "fibonacci",
+            "hanoi",
+            "hworld",
+            "loctest",
+            "name",
+            "sudoku"
+        ]
+    );
+    for result in &results {
+        assert!(result.passed(), "{}: {:?}", result.name, result.failure);
+    }
+}
+
+#[test]
+fn programs_without_fixtures_are_skipped() {
+    let dir = fixture_dir("skipped");
+    fs::write(dir.join("lonely.ws"), DIVIDE_BY_ZERO).unwrap();
+    fs::write(dir.join("lonely.in"), "").unwrap();
+
+    assert!(testing::discover(&dir).unwrap().is_empty());
+}
+
+#[test]
+fn expected_errors() {
+    let dir = fixture_dir("errors");
+    for (name, expected) in &[
+        ("right", "zero_division\n"),
+        ("any", ""),
+        ("wrong", "overflow"),
+    ] {
+        fs::write(dir.join(name).with_extension("ws"), DIVIDE_BY_ZERO).unwrap();
+        fs::write(dir.join(name).with_extension("err"), expected).unwrap();
+    }
+
+    let cases = testing::discover(&dir).unwrap();
+    let results = testing::run_all(&cases, &Config::default(), 1);
+    assert!(results[0].passed());
+    assert!(results[1].passed());
+    match &results[2].failure {
+        Some(Failure::WrongError { expected, actual }) => {
+            assert_eq!(expected, "overflow");
+            assert_eq!(actual, "zero_division");
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn runaway_programs_run_out_of_fuel() {
+    let dir = fixture_dir("fuel");
+    fs::write(dir.join("loop.ws"), INFINITE_LOOP).unwrap();
+    fs::write(dir.join("loop.out"), "").unwrap();
+
+    let cases = testing::discover(&dir).unwrap();
+    let config = Config {
+        fuel: Some(1000),
+        ..Config::default()
+    };
+    let result = testing::run_case(&cases[0], &config);
+    match result.failure {
+        Some(Failure::UnexpectedError(traceback)) => {
+            assert!(traceback.contains("budget was exhausted"), "{}", traceback);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn unified_diff() {
+    assert_eq!(testing::unified_diff("a\nb\n", "a\nb\n"), "");
+
+    let expected = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+    let actual = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12";
+    assert_eq!(
+        testing::unified_diff(expected, actual),
+        "\
+--- expected
++++ actual
+@@ -1,6 +1,6 @@
+ 1
+ 2
+-3
++three
+ 4
+ 5
+ 6
+@@ -9,4 +9,4 @@
+ 9
+ 10
+ 11
+-12
++12
+\\ No newline at end of file
+"
+    );
+}
+
+#[test]
+fn unified_diff_of_long_outputs() {
+    // Differences at both ends leave nothing to trim, which a quadratic
+    // table would need gigabytes for
+    let expected: String = (0..50_000).map(|x| format!("{}\n", x)).collect();
+    let actual = format!("first\n{}last\n", &expected[2..expected.len() - 6]);
+    let diff = testing::unified_diff(&expected, &actual);
+    assert!(
+        diff.contains("@@ -1,4 +1,4 @@\n-0\n+first\n 1\n"),
+        "{}",
+        diff
+    );
+    assert!(diff.ends_with(" 49998\n-49999\n+last\n"), "{}", diff);
+}
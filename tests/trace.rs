@@ -0,0 +1,101 @@
+//! Checks the per-instruction logs written by a `Tracer`.
+
+use std::io::{self, Cursor, Write};
+use whitespace_vm::asm::assemble;
+use whitespace_vm::{TraceFilter, TraceFormat, Tracer, Vm};
+
+const PROGRAM: &str = "\
+    push 5
+    push 1
+    readc
+    call show
+    end
+show:
+    push 1
+    retrieve
+    outc
+    ret
+";
+
+/// Runs `PROGRAM` with `A` as its input, returning the trace it logs
+fn trace(format: TraceFormat, filter: TraceFilter) -> String {
+    let program = assemble(PROGRAM).unwrap();
+    let mut log = vec![];
+    let tracer = Tracer::new(&mut log, format).with_filter(filter);
+    Vm::with_io(&program, Cursor::new("A"), vec![])
+        .with_tracer(tracer)
+        .run()
+        .unwrap();
+    String::from_utf8(log).unwrap()
+}
+
+#[test]
+fn human_trace() {
+    let log = trace(TraceFormat::Human, TraceFilter::default());
+    let lines: Vec<_> = log.lines().collect();
+    assert_eq!(lines.len(), 9);
+    assert!(lines[0].contains("push 5"));
+    assert!(lines[0].ends_with("[] -> [5]"));
+    assert!(lines[2].ends_with("[5 1] -> [5]  input \"A\"  heap[1] = 65"));
+    assert!(lines[6].ends_with("[5 65] -> [5]  output \"A\""));
+}
+
+#[test]
+fn json_trace() {
+    let log = trace(TraceFormat::Json, TraceFilter::default());
+    let first = log.lines().next().unwrap();
+    assert_eq!(
+        first,
+        "{\"step\":0,\"pc\":0,\"line\":1,\"column\":1,\"instruction\":\"push 5\",\
+         \"label\":null,\"stack_before\":[],\"depth_before\":0,\"stack_after\":[5],\
+         \"depth_after\":1,\"effects\":[],\"error\":null}"
+    );
+    assert!(log.contains("\"effects\":[{\"type\":\"output\",\"text\":\"A\"}]"));
+}
+
+#[test]
+fn filtered_trace() {
+    let program = assemble(PROGRAM).unwrap();
+    let show = program.find_label("show").unwrap();
+
+    let filter = TraceFilter {
+        lines: None,
+        labels: vec![show],
+    };
+    let log = trace(TraceFormat::Human, filter);
+    let instructions: Vec<_> = log.lines().map(|x| x.split_whitespace().nth(3)).collect();
+    assert_eq!(
+        instructions,
+        [Some("push"), Some("retrieve"), Some("outc"), Some("ret")]
+    );
+
+    let filter = TraceFilter {
+        lines: Some(2..=3),
+        labels: vec![],
+    };
+    assert_eq!(trace(TraceFormat::Human, filter).lines().count(), 2);
+}
+
+/// A trace output that fails every write
+struct Full;
+
+impl Write for Full {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::WriteZero.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn failing_trace_output_does_not_stop_the_program() {
+    let program = assemble(PROGRAM).unwrap();
+    let mut output = vec![];
+    Vm::with_io(&program, Cursor::new("A"), &mut output)
+        .with_tracer(Tracer::new(Full, TraceFormat::Human))
+        .run()
+        .unwrap();
+    assert_eq!(output, b"A");
+}
@@ -0,0 +1,349 @@
+//! Checks how the VM handles stack misuse, arithmetic overflow and its
+//! other runtime errors.
+
+use std::io::Cursor;
+use whitespace_vm::asm::assemble;
+use whitespace_vm::{Config, OverflowPolicy, Parser, RuntimeError, Traceback, Vm, WarningKind};
+
+/// Runs the assembly in `source`, returning the error it halts with
+fn run_error(source: &str) -> RuntimeError {
+    let program = assemble(source).unwrap();
+    match Vm::with_io(&program, Cursor::new(""), vec![]).run() {
+        Ok(outcome) => panic!("program succeeded with stack {:?}", outcome.stack),
+        Err(traceback) => traceback.reason,
+    }
+}
+
+#[test]
+fn copy_past_the_bottom_of_the_stack() {
+    assert_eq!(run_error("copy 0\nend"), RuntimeError::StackUnderflow);
+    assert_eq!(
+        run_error("push 5\ncopy 1\nend"),
+        RuntimeError::StackUnderflow
+    );
+    assert_eq!(
+        run_error("push 1\npush 2\ncopy 2\nend"),
+        RuntimeError::StackUnderflow
+    );
+}
+
+/// Runs the Whitespace `source`, which has parse errors, as far as it could
+/// be parsed, returning the error it halts with
+fn run_partial_error(source: &str) -> RuntimeError {
+    let output = Parser::new(source).parse_all();
+    assert!(!output.errors.is_empty());
+    match Vm::with_io(&output.program, Cursor::new(""), vec![]).run() {
+        Ok(outcome) => panic!("program succeeded with stack {:?}", outcome.stack),
+        Err(traceback) => traceback.reason,
+    }
+}
+
+#[test]
+fn negative_copy_and_slide_operands() {
+    // push 1, copy -1 or slide -1, end
+    assert_eq!(
+        run_partial_error("   \t\n \t \t\t\n\n\n\n"),
+        RuntimeError::StackUnderflow
+    );
+    assert_eq!(
+        run_partial_error("   \t\n \t\n\t\t\n\n\n\n"),
+        RuntimeError::StackUnderflow
+    );
+}
+
+#[test]
+fn slide_past_the_bottom_of_the_stack() {
+    assert_eq!(run_error("slide 0\nend"), RuntimeError::StackUnderflow);
+    assert_eq!(
+        run_error("push 1\npush 2\nslide 2\nend"),
+        RuntimeError::StackUnderflow
+    );
+
+    let program = assemble("push 1\npush 2\npush 3\nslide 2\nend").unwrap();
+    let outcome = Vm::with_io(&program, Cursor::new(""), vec![])
+        .run()
+        .unwrap();
+    let stack: Vec<_> = outcome.stack.iter().map(ToString::to_string).collect();
+    assert_eq!(stack, ["3"]);
+}
+
+#[test]
+fn copy_the_bottom_of_the_stack() {
+    let program = assemble("push 1\npush 2\ncopy 1\nend").unwrap();
+    let outcome = Vm::with_io(&program, Cursor::new(""), vec![])
+        .run()
+        .unwrap();
+    let stack: Vec<_> = outcome.stack.iter().map(ToString::to_string).collect();
+    assert_eq!(stack, ["1", "2", "1"]);
+}
+
+/// Runs the assembly in `source` with `config`, returning the final stack
+/// or the error it halts with
+fn run_with(source: &str, config: Config) -> Result<Vec<String>, RuntimeError> {
+    let program = assemble(source).unwrap();
+    match Vm::with_io(&program, Cursor::new(""), vec![])
+        .with_config(config)
+        .run()
+    {
+        Ok(outcome) => Ok(outcome.stack.iter().map(ToString::to_string).collect()),
+        Err(traceback) => Err(traceback.reason),
+    }
+}
+
+/// Applies the arithmetic instruction `op` to `left` and `right` under the
+/// given overflow policy
+#[cfg(not(feature = "bignum"))]
+fn arith(
+    op: &str,
+    left: i64,
+    right: i64,
+    overflow: OverflowPolicy,
+) -> Result<String, RuntimeError> {
+    let source = format!("push {}\npush {}\n{}\nend", left, right, op);
+    let config = Config {
+        overflow,
+        ..Config::default()
+    };
+    run_with(&source, config).map(|stack| stack.join(" "))
+}
+
+#[cfg(not(feature = "bignum"))]
+#[test]
+fn overflow_traps() {
+    let trap = |op, left, right| arith(op, left, right, OverflowPolicy::Trap);
+    assert_eq!(trap("add", i64::MAX, 1), Err(RuntimeError::Overflow));
+    assert_eq!(trap("sub", i64::MIN, 1), Err(RuntimeError::Overflow));
+    assert_eq!(trap("mul", i64::MAX, 2), Err(RuntimeError::Overflow));
+    assert_eq!(trap("div", i64::MIN, -1), Err(RuntimeError::Overflow));
+    // The exact remainder fits, even though computing it overflows `i64`
+    assert_eq!(trap("mod", i64::MIN, -1), Ok("0".to_string()));
+    assert_eq!(trap("add", i64::MAX - 1, 1), Ok(i64::MAX.to_string()));
+}
+
+#[cfg(not(feature = "bignum"))]
+#[test]
+fn overflow_wraps() {
+    let wrap = |op, left, right| arith(op, left, right, OverflowPolicy::Wrap).unwrap();
+    assert_eq!(wrap("add", i64::MAX, 1), i64::MIN.to_string());
+    assert_eq!(wrap("sub", i64::MIN, 1), i64::MAX.to_string());
+    assert_eq!(wrap("mul", i64::MAX, 2), "-2");
+    assert_eq!(wrap("div", i64::MIN, -1), i64::MIN.to_string());
+    assert_eq!(wrap("mod", i64::MIN, -1), "0");
+}
+
+#[cfg(not(feature = "bignum"))]
+#[test]
+fn overflow_saturates() {
+    let saturate = |op, left, right| arith(op, left, right, OverflowPolicy::Saturate).unwrap();
+    assert_eq!(saturate("add", i64::MAX, 1), i64::MAX.to_string());
+    assert_eq!(saturate("sub", i64::MIN, 1), i64::MIN.to_string());
+    assert_eq!(saturate("mul", i64::MIN, 2), i64::MIN.to_string());
+    assert_eq!(saturate("div", i64::MIN, -1), i64::MAX.to_string());
+    assert_eq!(saturate("mod", i64::MIN, -1), "0");
+}
+
+#[test]
+fn division_by_zero_ignores_the_overflow_policy() {
+    for overflow in [
+        OverflowPolicy::Trap,
+        OverflowPolicy::Wrap,
+        OverflowPolicy::Saturate,
+    ] {
+        let config = Config {
+            overflow,
+            ..Config::default()
+        };
+        for op in ["div", "mod"] {
+            let source = format!("push 1\npush 0\n{}\nend", op);
+            assert_eq!(
+                run_with(&source, config.clone()),
+                Err(RuntimeError::ZeroDivision)
+            );
+        }
+    }
+}
+
+/// Divides and takes the remainder of `left` and `right`
+fn div_mod(left: i64, right: i64) -> (String, String) {
+    let run = |op| {
+        let source = format!("push {}\npush {}\n{}\nend", left, right, op);
+        run_with(&source, Config::default()).unwrap().join(" ")
+    };
+    (run("div"), run("mod"))
+}
+
+#[cfg(not(feature = "bignum"))]
+#[test]
+fn division_truncates_towards_zero() {
+    assert_eq!(div_mod(7, 2), ("3".to_string(), "1".to_string()));
+    assert_eq!(div_mod(-7, 2), ("-3".to_string(), "-1".to_string()));
+    assert_eq!(div_mod(7, -2), ("-3".to_string(), "1".to_string()));
+    assert_eq!(div_mod(-7, -2), ("3".to_string(), "-1".to_string()));
+}
+
+#[cfg(feature = "bignum")]
+#[test]
+fn division_rounds_towards_negative_infinity() {
+    assert_eq!(div_mod(7, 2), ("3".to_string(), "1".to_string()));
+    assert_eq!(div_mod(-7, 2), ("-4".to_string(), "1".to_string()));
+    assert_eq!(div_mod(7, -2), ("-4".to_string(), "-1".to_string()));
+    assert_eq!(div_mod(-7, -2), ("3".to_string(), "-1".to_string()));
+}
+
+#[cfg(feature = "bignum")]
+#[test]
+fn values_beyond_i64() {
+    let source = format!(
+        "push {}\npush 1\nadd\npush {}\nmul\nend",
+        i64::MAX,
+        i64::MIN
+    );
+    assert_eq!(
+        run_with(&source, Config::default()).unwrap(),
+        ["-85070591730234615865843651857942052864"]
+    );
+
+    // Huge literals, heap addresses and division
+    let big = "123456789012345678901234567890";
+    let source = format!(
+        "push {big}\npush {big}\nstore\npush {big}\nretrieve\npush -{big}\ndiv\n\
+         push -{big}\npush 11\nmod\nend",
+        big = big
+    );
+    assert_eq!(run_with(&source, Config::default()).unwrap(), ["-1", "4"]);
+}
+
+#[cfg(feature = "bignum")]
+#[test]
+fn reads_and_prints_values_beyond_i64() {
+    let program = assemble("push 0\nreadn\npush 0\nretrieve\npush 2\nmul\noutn\nend").unwrap();
+    let mut output = vec![];
+    Vm::with_io(
+        &program,
+        Cursor::new("-99999999999999999999999\n"),
+        &mut output,
+    )
+    .run()
+    .unwrap();
+    assert_eq!(output, b"-199999999999999999999998");
+}
+
+/// Runs the assembly in `source` with `config`, returning the traceback it
+/// halts with
+fn traceback(source: &str, config: Config) -> Traceback {
+    let program = assemble(source).unwrap();
+    Vm::with_io(&program, Cursor::new(""), vec![])
+        .with_config(config)
+        .run()
+        .unwrap_err()
+}
+
+#[test]
+fn fuel_runs_out() {
+    let fuel = |fuel| Config {
+        fuel: Some(fuel),
+        ..Config::default()
+    };
+
+    let error = traceback("push 1\nloop:\npush 2\njmp loop", fuel(1000));
+    assert_eq!(error.reason, RuntimeError::FuelExhausted);
+    // The traceback shows where the program was looping, after the first
+    // instruction and 999 more in the loop
+    assert_eq!((error.pc, error.stack_depth), (2, 501));
+
+    // No fuel stops the program before its first instruction
+    let error = traceback("end", fuel(0));
+    assert_eq!((error.reason, error.pc), (RuntimeError::FuelExhausted, 0));
+
+    // Exactly enough fuel lets it finish
+    assert_eq!(run_with("push 1\nend", fuel(2)), Ok(vec!["1".to_string()]));
+    assert_eq!(
+        run_with("push 1\nend", fuel(1)),
+        Err(RuntimeError::FuelExhausted)
+    );
+}
+
+#[test]
+fn stack_limit() {
+    let config = Config {
+        max_stack: Some(2),
+        ..Config::default()
+    };
+    let error = traceback("push 1\npush 2\ndup\nend", config.clone());
+    assert_eq!((error.reason, error.pc), (RuntimeError::StackOverflow, 2));
+    assert_eq!(
+        run_with("push 1\npush 2\nend", config),
+        Ok(vec!["1".to_string(), "2".to_string()])
+    );
+}
+
+#[test]
+fn call_depth_limit() {
+    let config = Config {
+        max_call_depth: Some(3),
+        ..Config::default()
+    };
+    // main counts as a frame, so only two calls fit
+    let recurse = "call f\nend\nf:\ncall f\nret";
+    let error = traceback(recurse, config.clone());
+    assert_eq!(error.reason, RuntimeError::CallStackOverflow);
+    assert_eq!(error.stack.len(), 3);
+    assert_eq!(
+        run_with("call f\nend\nf:\ncall g\nret\ng:\nret", config),
+        Ok(vec![])
+    );
+}
+
+#[test]
+fn heap_limit() {
+    let config = Config {
+        max_heap: Some(2),
+        ..Config::default()
+    };
+    let store = |addr| format!("push {}\npush 0\nstore\n", addr);
+    // Overwriting an address already in use takes no more room
+    let source = format!("{}{}{}end", store(1), store(2), store(1));
+    assert_eq!(run_with(&source, config.clone()), Ok(vec![]));
+
+    let source = format!("{}{}{}end", store(1), store(2), store(3));
+    let error = traceback(&source, config);
+    assert_eq!(
+        (error.reason, error.pc),
+        (RuntimeError::HeapLimitExceeded, 8)
+    );
+}
+
+#[test]
+fn running_off_the_end() {
+    let error = traceback("push 1\npush 2", Config::default());
+    assert_eq!(error.reason, RuntimeError::MissingEnd);
+    assert_eq!((error.pc, error.instruction), (2, None));
+
+    let config = Config {
+        implicit_end: true,
+        ..Config::default()
+    };
+    assert_eq!(
+        run_with("push 1\npush 2", config.clone()),
+        Ok(vec!["1".to_string(), "2".to_string()])
+    );
+    assert_eq!(run_with("", config), Ok(vec![]));
+}
+
+#[test]
+fn falls_off_end_warning() {
+    let kinds = |source| {
+        let output = Parser::new(source).parse_all();
+        assert!(output.errors.is_empty());
+        let kinds: Vec<_> = output.warnings.iter().map(|x| x.kind()).collect();
+        kinds
+    };
+
+    // push 1, with no end
+    assert_eq!(kinds("   \t\n"), [WarningKind::FallsOffEnd]);
+    assert_eq!(kinds(""), [WarningKind::FallsOffEnd]);
+    // push 1, end
+    assert_eq!(kinds("   \t\n\n\n\n"), []);
+    // label 0, jmp 0, then a push 1 that is never reached
+    assert_eq!(kinds("\n   \n\n \n \n   \t\n"), []);
+}